@@ -0,0 +1,135 @@
+//! Origin-based CSRF protection, see `CsrfOriginGuard`.
+
+use iron::prelude::*;
+use iron::method::Method;
+use iron::status;
+use iron::headers::Referer;
+use iron::middleware::{AroundMiddleware, Handler};
+use std::collections::HashSet;
+use headers::OriginList;
+use exempt::is_exempt;
+use origin::Origin;
+
+/// Protects state-changing requests against Cross-Site Request Forgery by
+/// checking where they originate from.
+///
+/// CORS only restricts what a page may read, so a plain HTML form on a foreign
+/// site can still POST to this resource. For unsafe methods (`POST`, `PUT`,
+/// `PATCH` and `DELETE`) this middleware checks the `Origin` header, or the
+/// `Referer` header if no origin is sent, and responds with `403 Forbidden`
+/// unless the request comes from `own_origin` or one of the `trusted_origins`.
+/// Safe methods are always let through.
+///
+/// #Example
+/// ```
+/// extern crate iron;
+/// extern crate corsware;
+/// use corsware::{CsrfOriginGuard, Origin};
+/// use iron::prelude::*;
+/// use iron::status;
+///
+/// fn main() {
+///   let handler = |_: &mut Request| {
+///       Ok(Response::with((status::Ok, "Hello world!")))
+///   };
+///   let guard = CsrfOriginGuard {
+///     exempt_paths: vec!["/webhooks".to_owned()],
+///     ..CsrfOriginGuard::new(Origin::parse("https://www.a.com").unwrap())
+///   };
+///   let chain = guard.decorate(handler);
+///   let mut listening = Iron::new(chain).http("localhost:0").unwrap();
+///   listening.close().unwrap();
+/// }
+/// ```
+#[derive(Clone)]
+pub struct CsrfOriginGuard {
+    /// The origin this site is served from
+    pub own_origin: Origin,
+    /// Other origins allowed to make state-changing requests. Add
+    /// `Origin::Null` to accept requests from privacy-sensitive contexts.
+    pub trusted_origins: HashSet<Origin>,
+    /// Whether to let through unsafe requests carrying neither `Origin`
+    /// nor `Referer`. Browsers send at least one of them for cross-site
+    /// requests, while non-browser clients often send neither.
+    pub allow_missing: bool,
+    /// Paths which aren't checked, such as webhook endpoints. Each entry
+    /// matches the path itself and everything below it, so `/hooks` matches
+    /// `/hooks` and `/hooks/github`, but not `/hooksy`. Paths are as seen by
+    /// the middleware, i.e. without the prefix of any enclosing `Mount`.
+    pub exempt_paths: Vec<String>,
+}
+
+impl CsrfOriginGuard {
+    /// New guard accepting unsafe requests from the given origin only.
+    /// Rejects unsafe requests without `Origin` and `Referer`.
+    /// Exempts no paths.
+    pub fn new(own_origin: Origin) -> CsrfOriginGuard {
+        CsrfOriginGuard {
+            own_origin,
+            trusted_origins: HashSet::new(),
+            allow_missing: false,
+            exempt_paths: vec![],
+        }
+    }
+
+    /// Returns true for methods which are expected to change state
+    fn is_unsafe(method: &Method) -> bool {
+        matches!(*method, Method::Post | Method::Put | Method::Patch | Method::Delete)
+    }
+
+    fn is_trusted(&self, origin: &Origin) -> bool {
+        *origin == self.own_origin || self.trusted_origins.contains(origin)
+    }
+
+    fn handle(&self, req: &mut Request, handler: &dyn Handler) -> IronResult<Response> {
+        if !CsrfOriginGuard::is_unsafe(&req.method) || is_exempt(&self.exempt_paths, req) {
+            return handler.handle(req);
+        }
+        let source = match req.headers.get::<OriginList>() {
+            Some(origin) => {
+                match origin.single() {
+                    Some(origin) => {
+                        Some(("Origin", origin.to_owned(), Origin::parse_header(origin).ok()))
+                    }
+                    None => {
+                        return Ok(Response::with((status::Forbidden,
+                                                  "Unsafe request with multiple origins")))
+                    }
+                }
+            }
+            None => {
+                req.headers
+                    .get::<Referer>()
+                    .map(|referer| ("Referer", referer.0.clone(), Origin::parse(&referer.0).ok()))
+            }
+        };
+        match source {
+            None if self.allow_missing => handler.handle(req),
+            None => {
+                Ok(Response::with((status::Forbidden,
+                                   "Unsafe request without Origin or Referer header")))
+            }
+            Some((_, _, Some(ref origin))) if self.is_trusted(origin) => handler.handle(req),
+            Some((header, value, _)) => {
+                Ok(Response::with((status::Forbidden,
+                                   format!("Unsafe request from untrusted {} '{}'",
+                                           header,
+                                           value))))
+            }
+        }
+    }
+
+    /// Util function for wrapping the supplied handler with this guard.
+    /// Works by constructing a chain with only this middleware linked.
+    pub fn decorate<T: Handler>(self, handler: T) -> Chain {
+        let mut chain = Chain::new(handler);
+        chain.link_around(self);
+        chain
+    }
+}
+
+impl AroundMiddleware for CsrfOriginGuard {
+    fn around(self, handler: Box<dyn Handler>) -> Box<dyn Handler> {
+        Box::new(move |req: &mut Request| self.handle(req, &handler))
+    }
+}
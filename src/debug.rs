@@ -0,0 +1,113 @@
+//! Explanations of CORS decisions for debugging, see `CorsDebug`.
+
+use std::collections::HashSet;
+use origin::Origin;
+use reporting::json_string;
+use evaluate::CorsRequest;
+use CorsMiddleware;
+
+/// Explains CORS decisions to the client, in a response header and optionally
+/// in the body of rejections. See `CorsMiddleware::debug`.
+///
+/// Explanations reveal details of the policy, so they're only given to
+/// requests from `trusted_origins`, unless `trust_all` is set. Meant for
+/// staging environments.
+///
+/// The header holds `key=value` pairs separated by `; `, such as
+/// `origin=https://www.a.com; match=listed origin https://www.a.com;
+/// disallowed-headers=X-Custom; wildcard-suppressed=false`.
+#[derive(Clone)]
+pub struct CorsDebug {
+    /// The name of the response header holding the explanation
+    pub header_name: String,
+    /// The origins which are given explanations
+    pub trusted_origins: HashSet<Origin>,
+    /// If set, all origins are given explanations. Don't use in production.
+    pub trust_all: bool,
+    /// If set, rejected requests get a JSON body with the explanation, instead
+    /// of a plain text message
+    pub json_rejections: bool,
+}
+
+impl CorsDebug {
+    /// New configuration explaining decisions to the given origins, in the
+    /// `X-Cors-Debug` header only
+    pub fn new(trusted_origins: HashSet<Origin>) -> CorsDebug {
+        CorsDebug {
+            header_name: "X-Cors-Debug".to_owned(),
+            trusted_origins,
+            trust_all: false,
+            json_rejections: false,
+        }
+    }
+
+    /// Returns true if requests from the given origin are given explanations
+    pub(crate) fn trusts(&self, origin: &str) -> bool {
+        self.trust_all ||
+        Origin::parse_header(origin).is_ok_and(|o| self.trusted_origins.contains(&o))
+    }
+}
+
+/// An explanation of the decision for one request
+pub(crate) struct Explanation {
+    /// The origin as parsed and serialized, see `Origin::ascii_serialization()`
+    pub origin: String,
+    /// Which part of the policy matched the origin, or why none did
+    pub matched: String,
+    /// The requested headers which aren't allowed
+    pub disallowed_headers: Vec<String>,
+    /// Whether `*` wasn't used for Access-Control-Allow-Origin, despite
+    /// `prefer_wildcard`, since credentials are allowed
+    pub wildcard_suppressed: bool,
+}
+
+impl Explanation {
+    /// Formats the explanation as a header value
+    pub fn to_header(&self) -> String {
+        format!("origin={}; match={}; disallowed-headers={}; wildcard-suppressed={}",
+                self.origin,
+                self.matched,
+                self.disallowed_headers.join(","),
+                self.wildcard_suppressed)
+    }
+
+    /// Formats the explanation of a rejection as a JSON object
+    pub fn to_json(&self, message: &str) -> String {
+        format!("{{\"error\":{},\"origin\":{},\"match\":{},\"disallowed_headers\":[{}],\
+                 \"wildcard_suppressed\":{}}}",
+                json_string(message),
+                json_string(&self.origin),
+                json_string(&self.matched),
+                self.disallowed_headers
+                    .iter()
+                    .map(|h| json_string(h))
+                    .collect::<Vec<_>>()
+                    .join(","),
+                self.wildcard_suppressed)
+    }
+}
+
+impl CorsMiddleware {
+    /// Explains the decision for a CORS request, if debugging is enabled and
+    /// the origin is trusted
+    pub(crate) fn explanation(&self, req: &CorsRequest) -> Option<Explanation> {
+        let debug = self.debug.as_ref()?;
+        let origin = req.single_origin()??;
+        if !debug.trusts(origin) {
+            return None;
+        }
+        let (_, requested_headers) = req.requested();
+        Some(Explanation {
+            origin: Origin::parse_allow_opaque(origin)
+                .map(|o| o.ascii_serialization())
+                .unwrap_or_else(|_| origin.to_owned()),
+            matched: self.allowed_origins.explain(origin),
+            disallowed_headers: requested_headers.iter()
+                .filter(|h| !self.allowed_headers.contains(h))
+                .map(|h| h.to_string())
+                .collect(),
+            wildcard_suppressed: self.prefer_wildcard &&
+                                 self.credentials_for(origin) == Some(true),
+        })
+    }
+}
@@ -0,0 +1,656 @@
+//! The CORS decision of `CorsMiddleware`, independent of Iron, see
+//! `CorsMiddleware::evaluate`.
+
+use http::{HeaderMap, HeaderValue, Method, Request};
+use http::header::{HeaderName, ORIGIN, ACCESS_CONTROL_REQUEST_METHOD,
+                   ACCESS_CONTROL_REQUEST_HEADERS};
+#[cfg(any(feature = "hyper", feature = "tower"))]
+use http::header::UPGRADE;
+use unicase::UniCase;
+use std::time::SystemTime;
+use headers::TimingAllowOrigin;
+use observer::CorsDecision;
+use origin::{Origin, SyntaxError};
+use reporting::{Violation, ViolationKind, ViolationReport};
+use CorsMiddleware;
+
+/// The parts of a request which CORS decisions are based on
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CorsRequest {
+    /// The method of the request
+    pub method: Method,
+    /// The Origin header, if any. Several origins are separated by spaces.
+    pub origin: Option<String>,
+    /// The Access-Control-Request-Method header, if any
+    pub requested_method: Option<Method>,
+    /// The Access-Control-Request-Headers header, or empty if there's none
+    pub requested_headers: Vec<UniCase<String>>,
+}
+
+impl CorsRequest {
+    /// Extracts the parts of a request which CORS decisions are based on from
+    /// its method and headers. Repeated Origin headers are joined by spaces.
+    /// An Access-Control-Request-Method header which isn't a valid method is
+    /// treated as absent.
+    pub fn from_headers(method: Method, headers: &HeaderMap) -> CorsRequest {
+        let origins = headers.get_all(ORIGIN)
+            .iter()
+            .map(|v| String::from_utf8_lossy(v.as_bytes()).trim().to_owned())
+            .collect::<Vec<_>>();
+        CorsRequest {
+            method,
+            origin: if origins.is_empty() {
+                None
+            } else {
+                Some(origins.join(" "))
+            },
+            requested_method: headers.get(ACCESS_CONTROL_REQUEST_METHOD)
+                .and_then(|v| Method::from_bytes(v.as_bytes()).ok()),
+            requested_headers: headers.get_all(ACCESS_CONTROL_REQUEST_HEADERS)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .flat_map(|v| v.split(','))
+                .map(|h| h.trim())
+                .filter(|h| !h.is_empty())
+                .map(|h| UniCase(h.to_owned()))
+                .collect(),
+        }
+    }
+
+    /// Extracts the parts of a request which CORS decisions are based on
+    pub fn from_http<B>(req: &Request<B>) -> CorsRequest {
+        CorsRequest::from_headers(req.method().clone(), req.headers())
+    }
+
+    /// Returns true if this is a preflight request, i.e. an `OPTIONS` request
+    /// with an Access-Control-Request-Method header
+    pub fn is_preflight(&self) -> bool {
+        self.method == Method::OPTIONS && self.requested_method.is_some()
+    }
+
+    /// Returns the requested method and headers, i.e. Access-Control-Request-Method
+    /// and Access-Control-Request-Headers for preflight requests
+    pub(crate) fn requested(&self) -> (Method, Vec<UniCase<String>>) {
+        match self.requested_method {
+            Some(ref method) if self.is_preflight() => {
+                (method.clone(), self.requested_headers.clone())
+            }
+            _ => (self.method.clone(), vec![]),
+        }
+    }
+
+    /// Returns the single origin of the request, or `Some(None)` if there are
+    /// several. Repeated identical origins are collapsed, as for
+    /// `OriginList::single()`.
+    pub(crate) fn single_origin(&self) -> Option<Option<&str>> {
+        self.origin.as_ref().map(|value| {
+            let mut values = value.split(' ').filter(|v| !v.is_empty());
+            let first = values.next().unwrap_or("");
+            if values.all(|v| v == first) {
+                Some(first)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// The CORS headers to add to the response of an allowed request
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CorsHeaders {
+    /// Access-Control-Allow-Origin, i.e. the origin or `*`
+    pub allow_origin: String,
+    /// Whether to add Access-Control-Allow-Credentials
+    pub allow_credentials: bool,
+    /// Access-Control-Max-Age, for preflight requests only
+    pub max_age: Option<u32>,
+    /// Access-Control-Allow-Methods, for preflight requests only
+    pub allow_methods: Vec<Method>,
+    /// Access-Control-Allow-Headers, for preflight requests only
+    pub allow_headers: Vec<UniCase<String>>,
+    /// Access-Control-Expose-Headers, for normal requests only
+    pub expose_headers: Vec<UniCase<String>>,
+    /// Timing-Allow-Origin, for normal requests only
+    pub timing_allow_origin: Option<TimingAllowOrigin>,
+}
+
+impl CorsHeaders {
+    /// Returns the headers as names and values, in the order they're set.
+    /// Empty lists are left out.
+    pub fn to_pairs(&self) -> Vec<(&'static str, String)> {
+        fn join<T: ToString>(values: &[T]) -> String {
+            values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ")
+        }
+        let mut pairs = vec![];
+        if self.allow_credentials {
+            pairs.push(("Access-Control-Allow-Credentials", "true".to_owned()));
+        }
+        if let Some(ref timing) = self.timing_allow_origin {
+            pairs.push(("Timing-Allow-Origin", timing.to_string()));
+        }
+        pairs.push(("Access-Control-Allow-Origin", self.allow_origin.clone()));
+        if let Some(max_age) = self.max_age {
+            pairs.push(("Access-Control-Max-Age", max_age.to_string()));
+        }
+        if !self.allow_methods.is_empty() {
+            pairs.push(("Access-Control-Allow-Methods", join(&self.allow_methods)));
+        }
+        if !self.allow_headers.is_empty() {
+            pairs.push(("Access-Control-Allow-Headers", join(&self.allow_headers)));
+        }
+        if !self.expose_headers.is_empty() {
+            pairs.push(("Access-Control-Expose-Headers", join(&self.expose_headers)));
+        }
+        pairs
+    }
+
+    /// Returns the headers as a `HeaderMap`. Values which aren't valid header
+    /// values, such as configured header names with line breaks, are left out.
+    pub fn to_header_map(&self) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in self.to_pairs() {
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                map.append(HeaderName::from_bytes(name.as_bytes()).unwrap(), value);
+            }
+        }
+        map
+    }
+}
+
+/// A rejected CORS request
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CorsRejection {
+    /// Whether the request is a preflight request
+    pub preflight: bool,
+    /// What the request was rejected for
+    pub kind: ViolationKind,
+    /// A message describing the rejection, sent as the response body
+    pub message: String,
+}
+
+/// The decision for a request, see `CorsMiddleware::evaluate`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CorsOutcome {
+    /// The request has no Origin header, so it's not a CORS request and is
+    /// passed through without CORS headers
+    NotCors,
+    /// An allowed preflight request, answered with `204 No Content` and
+    /// these headers
+    Preflight(CorsHeaders),
+    /// An allowed normal request, passed to the handler. These headers are
+    /// added to successful responses.
+    Normal(CorsHeaders),
+    /// A rejected request, answered with `400 Bad Request`
+    Rejected(CorsRejection),
+}
+
+impl CorsMiddleware {
+    /// Decides how to respond to a request, without side effects such as
+    /// reporting, observers or logging. `handle()` is built on this, and it
+    /// can be used to apply the same policy outside of Iron.
+    ///
+    /// #Example
+    /// ```
+    /// extern crate corsware;
+    /// extern crate http;
+    /// use corsware::{CorsMiddleware, CorsRequest, CorsOutcome};
+    /// use http::{Method, Request};
+    ///
+    /// fn main() {
+    ///     let request = Request::builder()
+    ///         .method(Method::OPTIONS)
+    ///         .header("Origin", "http://a.com")
+    ///         .header("Access-Control-Request-Method", "PUT")
+    ///         .body(())
+    ///         .unwrap();
+    ///     let request = CorsRequest::from_http(&request);
+    ///     match CorsMiddleware::permissive().evaluate(&request) {
+    ///         CorsOutcome::Preflight(headers) => {
+    ///             assert_eq!(headers.allow_origin, "http://a.com")
+    ///         }
+    ///         _ => panic!("not allowed"),
+    ///     }
+    /// }
+    /// ```
+    pub fn evaluate(&self, req: &CorsRequest) -> CorsOutcome {
+        let preflight = req.is_preflight();
+        let request = if preflight { "Preflight" } else { "Normal" };
+        let reject = |kind: ViolationKind, message: String| {
+            CorsOutcome::Rejected(CorsRejection {
+                preflight,
+                kind,
+                message,
+            })
+        };
+        // - Preflight request
+        // - 1.If the Origin header is not present terminate this set of steps. The request is
+        // - outside the scope of this specification.
+        //
+        // Normal request
+        // - 1.If the Origin header is not present terminate this set of steps. The request is
+        // - outside the scope of this specification.
+        //
+        // Preflight requests without origin are rejected, while normal requests
+        // are treated as not being CORS requests. We could return error here if we
+        // wanted according to
+        // https://tools.ietf.org/id/draft-abarth-origin-03.html#rfc.section.6
+        //
+        // - 2.If the value of the Origin header is not a case-sensitive match for any of the
+        // - values in list of origins do not set any additional headers and terminate this
+        // - set of steps.
+        //
+        // - Note: Always matching is acceptable since the list of origins can be unbounded.
+        //
+        // - Note: The Origin header can only contain a single origin as the user agent
+        //       will not follow redirects.
+        //
+        // Multiple distinct origins can't be matched, and passing the request
+        // through as if there was no origin could be exploited
+        let origin_str = match req.single_origin() {
+            None if preflight => {
+                return reject(ViolationKind::MissingOrigin,
+                              "Preflight request without Origin header".to_owned())
+            }
+            None => return CorsOutcome::NotCors,
+            Some(None) => {
+                return reject(ViolationKind::MultipleOrigins,
+                              format!("{} request with multiple origins", request))
+            }
+            Some(Some(origin)) => origin,
+        };
+        // Unlike configured origins, the Origin header has to follow the
+        // grammar in RFC 6454 section 7 exactly
+        if let Err(e) = self.check_origin_header(origin_str) {
+            return reject(ViolationKind::MalformedOrigin,
+                          format!("{} request with malformed Origin header '{}': {}",
+                                  request,
+                                  origin_str,
+                                  e));
+        }
+        let credentials = self.credentials_for(origin_str);
+        let allowed_origin = match self.allowed_origins
+            .allowed_for(origin_str, credentials.unwrap_or(false), self.prefer_wildcard) {
+            Some(allowed_origin) => allowed_origin,
+            None => {
+                return reject(ViolationKind::Origin,
+                              format!("{} request requesting disallowed origin '{}'",
+                                      request,
+                                      origin_str))
+            }
+        };
+        // Credentials are only granted to secure origins if so configured
+        let credentials = match credentials {
+            Some(credentials) => credentials,
+            None => {
+                return reject(ViolationKind::InsecureOrigin,
+                              format!("{} request from insecure origin '{}' not allowed with \
+                                       credentials",
+                                      request,
+                                      origin_str))
+            }
+        };
+        if !preflight {
+            // - 3. If the resource supports credentials add a single
+            // - Access-Control-Allow-Origin
+            // - header, with the value of the Origin header as value, and add a single
+            // - Access-Control-Allow-Credentials header with the case-sensitive string
+            // - "true" as value.
+            //
+            // - Otherwise, add a single Access-Control-Allow-Origin header, with either the
+            // - value of the Origin header or the string "*" as value.
+            //
+            // - Note: The string "*" cannot be used for a resource that supports credentials.
+            //
+            // - 4. If the list of exposed headers is not empty add one or more
+            // - Access-Control-Expose-Headers headers, with as values the header field names
+            // - given in the list of exposed headers.
+            return CorsOutcome::Normal(CorsHeaders {
+                timing_allow_origin: self.timing_allow_origin(origin_str, &allowed_origin),
+                allow_origin: allowed_origin,
+                allow_credentials: credentials,
+                max_age: None,
+                allow_methods: vec![],
+                allow_headers: vec![],
+                expose_headers: self.exposed_headers.clone(),
+            });
+        }
+        //
+        // - 3. Let method be the value as result of parsing the Access-Control-Request-Method
+        // - header.
+        //
+        // - If there is no Access-Control-Request-Method header or if parsing failed, do not
+        // - set any additional headers and terminate this set of steps. The request is
+        // - outside the scope of this specification.
+
+        // We can assume that this header exists, since we already checked that before
+        // classifying the request as preflight
+        let requested_method = req.requested_method.as_ref().unwrap();
+        //
+        // - 4. Let header field-names be the values as result of parsing the
+        // - Access-Control-Request-Headers headers.
+        //
+        // - If there are no Access-Control-Request-Headers headers let header field-names be
+        // - the empty list.
+        //
+        // - If parsing failed do not set any additional headers and terminate this set of
+        // - steps. The request is outside the scope of this specification.
+        //
+        // - 5.If method is not a case-sensitive match for any of the values in list of
+        // -   methods do not set any additional headers and terminate this set of steps.
+        //
+        // - Always matching is acceptable since the list of methods can be unbounded.
+        //
+        if !self.allowed_methods.contains(requested_method) {
+            return reject(ViolationKind::Method,
+                          format!("Preflight request requesting disallowed method {}",
+                                  requested_method));
+        }
+        // - 6. If any of the header field-names is not a ASCII case-insensitive match for any
+        // - of the values in list of headers do not set any additional headers and terminate
+        // - this set of steps.
+        let mut disallowed_headers: Vec<&UniCase<String>> = vec![];
+        for header in &req.requested_headers {
+            if !self.allowed_headers.contains(header) && !disallowed_headers.contains(&header) {
+                disallowed_headers.push(header);
+            }
+        }
+        if !disallowed_headers.is_empty() {
+            return reject(ViolationKind::Headers,
+                          format!("Preflight request requesting disallowed header(s) {}",
+                                  disallowed_headers.iter()
+                                      .map(|h| h.to_string())
+                                      .collect::<Vec<_>>()
+                                      .join(",")));
+        }
+        //
+        // - Always matching is acceptable since the list of headers can be unbounded.
+        //
+        // - 7. If the resource supports credentials add a single Access-Control-Allow-Origin
+        // - header, with the value of the Origin header as value, and add a single
+        // - Access-Control-Allow-Credentials header with the case-sensitive string "true" as
+        // - value.
+        //
+        // - Otherwise, add a single Access-Control-Allow-Origin header, with either the
+        // - value of the Origin header or the string "*" as value.
+        //
+        // - The string "*" cannot be used for a resource that supports credentials.
+        //
+        // - 8. Optionally add a single Access-Control-Max-Age header with as value the amount
+        // - of seconds the user agent is allowed to cache the result of the request.
+        //
+        // - 9. If method is a simple method this step may be skipped.
+        //
+        // - Add one or more Access-Control-Allow-Methods headers consisting of (a subset of)
+        // - the list of methods.
+        //
+        // - If a method is a simple method it does not need to be listed, but this is not
+        // - prohibited.
+        //
+        // - Since the list of methods can be unbounded, simply returning the method
+        // - indicated by Access-Control-Request-Method (if supported) can be enough.
+        //
+        // - 10.If each of the header field-names is a simple header and none is Content-Type,
+        // - this step may be skipped.
+        //
+        // - Add one or more Access-Control-Allow-Headers headers consisting of (a subset of)
+        // - the list of headers.
+        //
+        // - If a header field name is a simple header and is not Content-Type, it is not
+        // - required to be listed. Content-Type is to be listed as only a subset of its
+        // - values makes it qualify as simple header.
+        //
+        // - Since the list of headers can be unbounded, simply returning supported headers
+        // - from Access-Control-Allow-Headers can be enough.
+        CorsOutcome::Preflight(CorsHeaders {
+            allow_origin: allowed_origin,
+            allow_credentials: credentials,
+            max_age: Some(self.max_age_seconds),
+            allow_methods: self.allowed_methods.clone(),
+            allow_headers: self.allowed_headers.clone(),
+            expose_headers: vec![],
+            timing_allow_origin: None,
+        })
+    }
+
+    /// Checks the Origin header against the grammar in RFC 6454 section 7.
+    /// Values outside the grammar with an opaque origin, such as `data:` URLs,
+    /// are accepted as well if the allowed origins include opaque origins.
+    fn check_origin_header(&self, origin_str: &str) -> Result<(), SyntaxError> {
+        match Origin::parse_header(origin_str) {
+            Ok(_) => Ok(()),
+            Err(_) if self.allowed_origins.allows_opaque() &&
+                      Origin::parse_allow_opaque(origin_str).is_ok_and(|o| o.is_opaque()) => {
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Checks the origin of a WebSocket handshake the same way as for CORS
+    /// requests, see `guard_websockets`. Returns the message to reject the
+    /// handshake with `403 Forbidden`, if it's rejected.
+    #[cfg(any(feature = "hyper", feature = "tower"))]
+    pub(crate) fn check_websocket(&self, req: &CorsRequest) -> Result<(), String> {
+        let origin_str = match req.single_origin() {
+            // Only browsers are required to send Origin, let other clients through
+            None => return Ok(()),
+            Some(None) => return Err("WebSocket handshake with multiple origins".to_owned()),
+            Some(Some(origin)) => origin,
+        };
+        if let Err(e) = self.check_origin_header(origin_str) {
+            return Err(format!("WebSocket handshake with malformed Origin header '{}': {}",
+                               origin_str,
+                               e));
+        }
+        // Cookies are sent with the handshake, so the credentials policy applies as well
+        let credentials = self.credentials_for(origin_str);
+        if credentials.is_none() ||
+           self.allowed_origins.allowed_for(origin_str, false, false).is_none() {
+            return Err(format!("WebSocket handshake from disallowed origin '{}'", origin_str));
+        }
+        Ok(())
+    }
+
+    /// Evaluates the report-only candidate policy, if any, for CORS requests
+    /// with a well-formed origin
+    pub(crate) fn evaluate_candidate(&self,
+                                     req: &CorsRequest,
+                                     outcome: &CorsOutcome,
+                                     path: &str) {
+        let candidate = match self.report_only {
+            Some(ref candidate) => candidate,
+            None => return,
+        };
+        let origin = match req.single_origin() {
+            Some(Some(origin)) => origin,
+            _ => return,
+        };
+        if let CorsOutcome::Rejected(ref rejection) = *outcome {
+            if rejection.kind == ViolationKind::MalformedOrigin {
+                return;
+            }
+        }
+        let (method, headers) = req.requested();
+        let violation = candidate.evaluate(origin, &method, &headers, req.is_preflight());
+        if let (Some(violation), Some(reporter)) = (violation, self.violation_reporter.as_ref()) {
+            reporter.report(&ViolationReport {
+                timestamp: SystemTime::now(),
+                policy: "report-only".to_owned(),
+                path: path.to_owned(),
+                violation,
+            });
+        }
+    }
+
+    /// Returns the violation of a rejected request
+    pub(crate) fn violation(&self, req: &CorsRequest, rejection: &CorsRejection) -> Violation {
+        let (method, headers) = req.requested();
+        Violation {
+            preflight: rejection.preflight,
+            origin: req.origin.clone().unwrap_or_default(),
+            method,
+            headers,
+            kind: rejection.kind,
+        }
+    }
+
+    /// Reports a violation of the enforced policy to `violation_reporter`, if any
+    pub(crate) fn report_violation(&self, path: &str, violation: &Violation) {
+        if let Some(ref reporter) = self.violation_reporter {
+            reporter.report(&ViolationReport {
+                timestamp: SystemTime::now(),
+                policy: "enforced".to_owned(),
+                path: path.to_owned(),
+                violation: violation.clone(),
+            });
+        }
+    }
+
+    /// Notifies the observers of the decision for a request
+    fn observe(&self, req: &CorsRequest, path: &str, outcome: &CorsOutcome) {
+        if self.observers.is_empty() {
+            return;
+        }
+        let headers = match *outcome {
+            CorsOutcome::NotCors => {
+                for observer in &self.observers {
+                    observer.on_passthrough_no_origin(req, path);
+                }
+                return;
+            }
+            CorsOutcome::Rejected(ref rejection) => {
+                let violation = self.violation(req, rejection);
+                for observer in &self.observers {
+                    observer.on_rejected(req, path, &violation);
+                }
+                return;
+            }
+            CorsOutcome::Preflight(ref headers) |
+            CorsOutcome::Normal(ref headers) => headers,
+        };
+        let (method, requested_headers) = req.requested();
+        let decision = CorsDecision {
+            origin: req.single_origin().flatten().unwrap_or_default().to_owned(),
+            method,
+            headers: requested_headers,
+            allow_origin: headers.allow_origin.clone(),
+            allow_credentials: headers.allow_credentials,
+        };
+        for observer in &self.observers {
+            if req.is_preflight() {
+                observer.on_preflight(req, path, &decision);
+            } else {
+                observer.on_normal(req, path, &decision);
+            }
+        }
+    }
+
+    /// Decides how to respond to a request like `evaluate()`, with the side
+    /// effects of the Iron middleware: the candidate policy is evaluated,
+    /// decisions are logged, rejections are reported to `violation_reporter`
+    /// and the observers are notified. For adapting the policy to other
+    /// frameworks, together with `response_headers()` and `rejection_body()`.
+    pub fn process(&self, req: &CorsRequest, path: &str) -> CorsOutcome {
+        let outcome = self.evaluate(req);
+        self.evaluate_candidate(req, &outcome, path);
+        match outcome {
+            CorsOutcome::NotCors => {
+                log_debug!("Passed through request to {} without Origin header", path);
+            }
+            CorsOutcome::Preflight(ref headers) |
+            CorsOutcome::Normal(ref headers) => self.log_allowed(req, path, headers),
+            CorsOutcome::Rejected(ref rejection) => {
+                self.log_rejection(req, path, rejection);
+                if self.violation_reporter.is_some() {
+                    self.report_violation(path, &self.violation(req, rejection));
+                }
+            }
+        }
+        self.observe(req, path, &outcome);
+        outcome
+    }
+
+    /// Returns the headers to set on every response to a request, whether
+    /// it's a CORS request or not: `Vary`, `Cross-Origin-Resource-Policy`
+    /// and the debug header, if any. `Cross-Origin-Resource-Policy` shouldn't
+    /// replace a value set by the handler.
+    pub fn response_headers(&self, req: &CorsRequest) -> Vec<(String, String)> {
+        // Vary-Headers are outside the CORS specification, but still important for
+        // caching. These should be set unconditionally for all resources covered by CORS
+        let mut headers = vec![("Vary".to_owned(),
+                                "Origin, Access-Control-Request-Method, \
+                                 Access-Control-Request-Headers"
+                                    .to_owned())];
+        if let Some(policy) = self.cross_origin_resource_policy() {
+            headers.push(("Cross-Origin-Resource-Policy".to_owned(), policy.to_string()));
+        }
+        if let Some(explanation) = self.explanation(req) {
+            let name = self.debug.as_ref().unwrap().header_name.clone();
+            headers.push((name, explanation.to_header()));
+        }
+        headers
+    }
+
+    /// Returns the body of the `400 Bad Request` response to a rejected
+    /// request, and its content type. The body is a JSON explanation if
+    /// `CorsDebug::json_rejections` is set and the origin is trusted, and the
+    /// message of the rejection otherwise.
+    pub fn rejection_body(&self,
+                          req: &CorsRequest,
+                          rejection: &CorsRejection)
+                          -> (String, &'static str) {
+        match self.explanation(req) {
+            Some(ref explanation) if self.debug.as_ref().unwrap().json_rejections => {
+                (explanation.to_json(&rejection.message), "application/json")
+            }
+            _ => (rejection.message.clone(), "text/plain"),
+        }
+    }
+
+    /// Logs an allowed request at `debug`
+    fn log_allowed(&self, req: &CorsRequest, path: &str, headers: &CorsHeaders) {
+        let origin = req.single_origin().flatten().unwrap_or_default();
+        log_debug!("Allowed {} request to {} from origin {:?} by {}: \
+                    Access-Control-Allow-Origin {}, credentials {}",
+                   if req.is_preflight() { "preflight" } else { "normal" },
+                   path,
+                   Origin::parse_allow_opaque(origin),
+                   self.allowed_origins.explain(origin),
+                   headers.allow_origin,
+                   headers.allow_credentials);
+    }
+
+    /// Logs a rejection, at `info` and the matching of its origin at `debug`
+    pub(crate) fn log_rejection(&self, req: &CorsRequest, path: &str, rejection: &CorsRejection) {
+        log_info!("Rejected {} request to {}: {}",
+                  if rejection.preflight { "preflight" } else { "normal" },
+                  path,
+                  rejection.message);
+        if let Some(Some(origin)) = req.single_origin() {
+            log_debug!("Origin {:?}: {}",
+                       Origin::parse_allow_opaque(origin),
+                       self.allowed_origins.explain(origin));
+        }
+    }
+}
+
+/// Returns true if a header returned by `CorsMiddleware::response_headers()`
+/// is to be set on a response, given whether the response already has a
+/// `Cross-Origin-Resource-Policy`. One set by the handler is kept.
+#[cfg(any(feature = "iron", feature = "tower"))]
+pub(crate) fn sets_response_header(name: &str, has_resource_policy: bool) -> bool {
+    !(has_resource_policy && name.eq_ignore_ascii_case("Cross-Origin-Resource-Policy"))
+}
+
+/// Returns true if the request asks to be upgraded to a WebSocket, i.e. if
+/// `Upgrade` lists the `websocket` protocol, in any case and with or without
+/// version. Used by all adapters, so that they guard the same requests.
+#[cfg(any(feature = "hyper", feature = "tower"))]
+pub(crate) fn is_websocket_handshake(headers: &HeaderMap) -> bool {
+    headers.get_all(UPGRADE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        // Protocols may carry a version, such as `websocket/13`
+        .any(|p| p.trim().split('/').next().unwrap_or("").eq_ignore_ascii_case("websocket"))
+}
@@ -0,0 +1,13 @@
+//! Path exemptions shared by the Iron guards.
+
+use iron::prelude::*;
+
+/// Returns true if the path of the request is one of the exempt paths, or
+/// below one of them
+pub(crate) fn is_exempt(exempt_paths: &[String], req: &Request) -> bool {
+    let path = format!("/{}", req.url.path().join("/"));
+    exempt_paths.iter().any(|exempt| {
+        let exempt = exempt.trim_end_matches('/');
+        path == exempt || path.starts_with(&format!("{}/", exempt))
+    })
+}
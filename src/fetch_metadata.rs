@@ -0,0 +1,155 @@
+//! Resource isolation based on Fetch Metadata, see `ResourceIsolationPolicy`.
+
+use iron::prelude::*;
+use iron::method::Method;
+use iron::status;
+use iron::middleware::{AroundMiddleware, Handler};
+use headers::{OriginList, SecFetchSite, SecFetchMode, SecFetchDest};
+use exempt::is_exempt;
+use origin::Origin;
+
+/// Rejects unwanted cross-site requests based on the `Sec-Fetch-*` headers
+/// sent by modern browsers, protecting against CSRF, XSSI and cross-site
+/// leaks. See https://web.dev/articles/fetch-metadata
+///
+/// Requests from the same origin, and requests initiated by the user
+/// (`Sec-Fetch-Site: none`) are always allowed. Same-site requests and
+/// top-level navigations are allowed by default as well. Everything else
+/// from another site is rejected with `403 Forbidden`. Requests without
+/// `Sec-Fetch-Site` come from older browsers or non-browser clients, and are
+/// let through.
+///
+/// #Example
+/// ```
+/// extern crate iron;
+/// extern crate corsware;
+/// use corsware::{ResourceIsolationPolicy, Origin};
+/// use iron::prelude::*;
+/// use iron::status;
+///
+/// fn main() {
+///   let handler = |_: &mut Request| {
+///       Ok(Response::with((status::Ok, "Hello world!")))
+///   };
+///   let policy = ResourceIsolationPolicy {
+///     exempt_paths: vec!["/public".to_owned()],
+///     ..ResourceIsolationPolicy::new(Origin::parse("https://www.a.com").unwrap())
+///   };
+///   let chain = policy.decorate(handler);
+///   let mut listening = Iron::new(chain).http("localhost:0").unwrap();
+///   listening.close().unwrap();
+/// }
+/// ```
+#[derive(Clone)]
+pub struct ResourceIsolationPolicy {
+    /// The origin this site is served from. Used to detect requests whose
+    /// `Sec-Fetch-Site` contradicts their `Origin` header.
+    pub own_origin: Origin,
+    /// Whether to allow requests from other origins on the same site
+    pub allow_same_site: bool,
+    /// Whether to allow cross-site top-level navigations, i.e. `GET` and
+    /// `HEAD` requests in `navigate` mode, which aren't destined for an
+    /// `<object>` or `<embed>`. Needed for links from other sites to work.
+    pub allow_navigations: bool,
+    /// Whether to reject requests claiming to be more trusted in
+    /// `Sec-Fetch-Site` than their `Origin` header shows, such as a
+    /// `same-origin` request with a foreign origin. Browsers never send such
+    /// requests, so they indicate a forged or tampered header.
+    pub reject_contradictions: bool,
+    /// Paths which aren't checked, such as resources meant to be embedded
+    /// by other sites. Each entry matches the path itself and everything
+    /// below it. Paths are as seen by the middleware, i.e. without the prefix
+    /// of any enclosing `Mount`.
+    pub exempt_paths: Vec<String>,
+}
+
+impl ResourceIsolationPolicy {
+    /// New policy for a site served from the given origin.
+    /// Allows same-site requests.
+    /// Allows cross-site top-level navigations.
+    /// Rejects requests with contradicting metadata.
+    /// Exempts no paths.
+    pub fn new(own_origin: Origin) -> ResourceIsolationPolicy {
+        ResourceIsolationPolicy {
+            own_origin,
+            allow_same_site: true,
+            allow_navigations: true,
+            reject_contradictions: true,
+            exempt_paths: vec![],
+        }
+    }
+
+    /// Returns true if the `Origin` of the request shows that it's less
+    /// trusted than what `Sec-Fetch-Site` claims.
+    fn contradicts_origin(&self, req: &Request, site: SecFetchSite) -> bool {
+        let origin = req.headers
+            .get::<OriginList>()
+            .and_then(|h| h.single())
+            .and_then(|o| Origin::parse_header(o).ok());
+        match origin {
+            Some(ref origin) if origin.is_tuple() => {
+                match site {
+                    SecFetchSite::SameOrigin => *origin != self.own_origin,
+                    SecFetchSite::SameSite => !origin.is_same_site(&self.own_origin),
+                    _ => false,
+                }
+            }
+            // Nothing to compare with. Null origins are sent after cross-origin
+            // redirects, where the metadata refers to the whole redirect chain.
+            _ => false,
+        }
+    }
+
+    fn is_navigation(req: &Request) -> bool {
+        req.headers.get::<SecFetchMode>() == Some(&SecFetchMode::Navigate) &&
+        (req.method == Method::Get || req.method == Method::Head) &&
+        !matches!(req.headers.get::<SecFetchDest>(),
+                  Some(&SecFetchDest::Object) | Some(&SecFetchDest::Embed))
+    }
+
+    fn handle(&self, req: &mut Request, handler: &dyn Handler) -> IronResult<Response> {
+        if is_exempt(&self.exempt_paths, req) {
+            return handler.handle(req);
+        }
+        let site = match req.headers.get::<SecFetchSite>() {
+            Some(site) => *site,
+            None => return handler.handle(req),
+        };
+        if self.reject_contradictions && self.contradicts_origin(req, site) {
+            return Ok(Response::with((status::Forbidden,
+                                      format!("Sec-Fetch-Site '{}' contradicts Origin header",
+                                              site))));
+        }
+        let allowed = match site {
+            SecFetchSite::SameOrigin | SecFetchSite::None => true,
+            SecFetchSite::SameSite => {
+                self.allow_same_site ||
+                (self.allow_navigations && ResourceIsolationPolicy::is_navigation(req))
+            }
+            SecFetchSite::CrossSite => {
+                self.allow_navigations && ResourceIsolationPolicy::is_navigation(req)
+            }
+        };
+        if allowed {
+            handler.handle(req)
+        } else {
+            Ok(Response::with((status::Forbidden,
+                               format!("{} request rejected by resource isolation policy",
+                                       site))))
+        }
+    }
+
+    /// Util function for wrapping the supplied handler with this policy.
+    /// Works by constructing a chain with only this middleware linked.
+    pub fn decorate<T: Handler>(self, handler: T) -> Chain {
+        let mut chain = Chain::new(handler);
+        chain.link_around(self);
+        chain
+    }
+}
+
+impl AroundMiddleware for ResourceIsolationPolicy {
+    fn around(self, handler: Box<dyn Handler>) -> Box<dyn Handler> {
+        Box::new(move |req: &mut Request| self.handle(req, &handler))
+    }
+}
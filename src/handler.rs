@@ -0,0 +1,157 @@
+//! A plain hyper 0.10 handler applying `CorsMiddleware`, enabled by the `hyper`
+//! feature. The Iron adapter shares its hyper helpers.
+
+use hyper::server::{Handler, Request, Response};
+use hyper::header::{Headers, ContentType};
+use hyper::method::Method;
+use hyper::net::Fresh;
+use hyper::status::StatusCode;
+use hyper::uri::RequestUri;
+use http::{HeaderMap, HeaderValue};
+use http::header::{ORIGIN, ACCESS_CONTROL_REQUEST_METHOD, ACCESS_CONTROL_REQUEST_HEADERS,
+                   UPGRADE};
+use evaluate::{CorsRequest, CorsOutcome, CorsHeaders, is_websocket_handshake};
+use CorsMiddleware;
+
+/// Wraps a hyper 0.10 `Handler` which isn't built on Iron, applying the policy
+/// of a `CorsMiddleware` with the same semantics as the Iron middleware.
+/// Preflight requests and rejected requests are answered without calling the
+/// wrapped handler.
+///
+/// Since hyper handlers write their responses themselves, the CORS headers are
+/// set on the response before it's passed to the wrapped handler. They can be
+/// read back as the usual typed headers, such as `AccessControlAllowOrigin`
+/// and `Vary`. A handler which sets `Vary` should extend the value which is
+/// already there, and a `Cross-Origin-Resource-Policy` set by the handler
+/// replaces the one of the middleware. Observers aren't notified, since they
+/// take Iron requests.
+///
+/// #Example
+/// ```no_run
+/// extern crate corsware;
+/// extern crate hyper;
+/// use corsware::{CorsMiddleware, CorsHandler};
+/// use hyper::server::{Server, Request, Response};
+///
+/// fn main() {
+///     let handler = |_: Request, res: Response| {
+///         res.send(b"Hello world!").unwrap();
+///     };
+///     let cors = CorsHandler::new(CorsMiddleware::permissive(), handler);
+///     Server::http("localhost:3000").unwrap().handle(cors).unwrap();
+/// }
+/// ```
+pub struct CorsHandler<H> {
+    cors: CorsMiddleware,
+    handler: H,
+}
+
+impl<H: Handler> CorsHandler<H> {
+    /// New handler applying the policy of the given middleware to the given
+    /// handler
+    pub fn new(cors: CorsMiddleware, handler: H) -> CorsHandler<H> {
+        CorsHandler { cors, handler }
+    }
+}
+
+impl<H: Handler> Handler for CorsHandler<H> {
+    fn handle<'a, 'k>(&'a self, req: Request<'a, 'k>, mut res: Response<'a, Fresh>) {
+        let headers = cors_headers(&req.headers);
+        let cors_req = match cors_request(&req.method, &headers) {
+            Some(cors_req) => cors_req,
+            None => return self.handler.handle(req, res),
+        };
+        if self.cors.guard_websockets && is_websocket_handshake(&headers) {
+            // No CORS headers are added to handshake responses
+            match self.cors.check_websocket(&cors_req) {
+                Ok(()) => self.handler.handle(req, res),
+                Err(message) => send(res, StatusCode::Forbidden, &message),
+            }
+            return;
+        }
+        let outcome = self.cors.process(&cors_req, &path(&req.uri));
+        for (name, value) in self.cors.response_headers(&cors_req) {
+            res.headers_mut().set_raw(name, vec![value.into_bytes()]);
+        }
+        match outcome {
+            CorsOutcome::NotCors => self.handler.handle(req, res),
+            CorsOutcome::Normal(headers) => {
+                set_headers(res.headers_mut(), &headers);
+                self.handler.handle(req, res)
+            }
+            CorsOutcome::Preflight(headers) => {
+                set_headers(res.headers_mut(), &headers);
+                // Successful preflight status code is NoContent
+                send(res, StatusCode::NoContent, "")
+            }
+            CorsOutcome::Rejected(rejection) => {
+                let (body, content_type) = self.cors.rejection_body(&cors_req, &rejection);
+                if content_type == "application/json" {
+                    res.headers_mut().set(ContentType::json());
+                }
+                send(res, StatusCode::BadRequest, &body)
+            }
+        }
+    }
+
+    fn check_continue(&self, head: (&Method, &RequestUri, &Headers)) -> StatusCode {
+        self.handler.check_continue(head)
+    }
+
+    fn on_connection_start(&self) {
+        self.handler.on_connection_start()
+    }
+
+    fn on_connection_end(&self) {
+        self.handler.on_connection_end()
+    }
+}
+
+/// Copies the headers which the CORS decision is based on, including
+/// `Upgrade` for detecting WebSocket handshakes
+pub(crate) fn cors_headers(headers: &Headers) -> HeaderMap {
+    let mut map = HeaderMap::new();
+    for name in &[ORIGIN, ACCESS_CONTROL_REQUEST_METHOD, ACCESS_CONTROL_REQUEST_HEADERS, UPGRADE] {
+        for line in headers.get_raw(name.as_str()).unwrap_or(&[]) {
+            if let Ok(value) = HeaderValue::from_bytes(line) {
+                map.append(name, value);
+            }
+        }
+    }
+    map
+}
+
+/// Returns the parts of the request which the CORS decision is based on,
+/// given the headers copied by `cors_headers()`. Returns `None` if the method
+/// can't be represented as an `http::Method`, such as an extension method
+/// which isn't a valid token. Such requests are passed through, since browsers
+/// only send CORS requests with valid methods.
+pub(crate) fn cors_request(method: &Method, headers: &HeaderMap) -> Option<CorsRequest> {
+    let method = method.as_ref().parse().ok()?;
+    Some(CorsRequest::from_headers(method, headers))
+}
+
+/// Sets the CORS headers of an allowed request on a response
+pub(crate) fn set_headers(headers: &mut Headers, cors_headers: &CorsHeaders) {
+    for (name, value) in cors_headers.to_pairs() {
+        headers.set_raw(name, vec![value.into_bytes()]);
+    }
+}
+
+/// Returns the path of the request, without the query
+fn path(uri: &RequestUri) -> String {
+    match *uri {
+        RequestUri::AbsolutePath(ref path) => path.split('?').next().unwrap_or("").to_owned(),
+        RequestUri::AbsoluteUri(ref url) => url.path().to_owned(),
+        RequestUri::Authority(ref authority) => authority.clone(),
+        RequestUri::Star => "*".to_owned(),
+    }
+}
+
+/// Sends a response decided without calling the wrapped handler
+fn send(mut res: Response<Fresh>, status: StatusCode, body: &str) {
+    *res.status_mut() = status;
+    if let Err(e) = res.send(body.as_bytes()) {
+        log_debug!("Failed to send response: {}", e);
+    }
+}
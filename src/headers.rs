@@ -0,0 +1,547 @@
+//! Typed headers not provided by Iron. The types are always available, while
+//! their hyper `Header` implementations require the `hyper` feature.
+
+#[cfg(feature = "hyper")]
+use hyper;
+#[cfg(feature = "hyper")]
+use hyper::header::{Header, HeaderFormat, parsing};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::str;
+
+/// A Custom Origin header which allows for null origins, something the standard
+/// Iron header does not.
+///
+/// Holds the value as sent, which might be a space-separated list of origins, and
+/// fails to parse if the request carries the header more than once. Use
+/// `OriginList` to detect both cases.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OriginHeader(pub String);
+
+impl Deref for OriginHeader {
+    type Target = String;
+
+    fn deref(&self) -> &String {
+        &self.0
+    }
+}
+
+impl DerefMut for OriginHeader {
+    fn deref_mut(&mut self) -> &mut String {
+        &mut self.0
+    }
+}
+
+#[cfg(feature = "hyper")]
+impl Header for OriginHeader {
+    fn header_name() -> &'static str {
+        "Origin"
+    }
+
+    fn parse_header(raw: &[Vec<u8>]) -> hyper::Result<OriginHeader> {
+        parsing::from_one_raw_str(raw).map(OriginHeader)
+    }
+}
+
+#[cfg(feature = "hyper")]
+impl HeaderFormat for OriginHeader {
+    fn fmt_header(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Display for OriginHeader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// The Origin header as a list of origins, allowing for null origins as well.
+///
+/// RFC 6454 allows the header to hold a space-separated list of origins, and a
+/// request might carry the header more than once. All the values are kept in
+/// order. Use `OriginList::single()` to get the one origin of the request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OriginList(pub Vec<String>);
+
+impl OriginList {
+    /// Returns the origin of the request, or `None` if the header holds
+    /// several different origins. Repeated identical values are collapsed,
+    /// since they're unambiguous.
+    ///
+    /// #Examples
+    /// ```
+    /// use corsware::OriginList;
+    /// let single = OriginList(vec!["http://a.com".to_owned()]);
+    /// assert_eq!(single.single(), Some("http://a.com"));
+    /// let same = OriginList(vec!["http://a.com".to_owned(), "http://a.com".to_owned()]);
+    /// assert_eq!(same.single(), Some("http://a.com"));
+    /// let different = OriginList(vec!["http://a.com".to_owned(), "http://b.com".to_owned()]);
+    /// assert_eq!(different.single(), None);
+    /// ```
+    pub fn single(&self) -> Option<&str> {
+        match self.0.first() {
+            Some(first) if self.0.iter().all(|v| v == first) => Some(first),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "hyper")]
+impl Header for OriginList {
+    fn header_name() -> &'static str {
+        "Origin"
+    }
+
+    fn parse_header(raw: &[Vec<u8>]) -> hyper::Result<OriginList> {
+        let mut values = vec![];
+        for line in raw {
+            let line = str::from_utf8(line)?.trim();
+            // origin-list = serialized-origin *( SP serialized-origin )
+            values.extend(line.split(' ').filter(|v| !v.is_empty()).map(|v| v.to_owned()));
+        }
+        match (raw.len(), values.len()) {
+            (0, _) => Err(hyper::Error::Header),
+            // A single empty value is kept, to be rejected as malformed
+            (1, 0) => Ok(OriginList(vec!["".to_owned()])),
+            _ => Ok(OriginList(values)),
+        }
+    }
+}
+
+#[cfg(feature = "hyper")]
+impl HeaderFormat for OriginList {
+    fn fmt_header(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Display for OriginList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0.join(" "))
+    }
+}
+
+/// Defines an enum of tokens out of a fixed set, which can be parsed from
+/// and formatted as a string.
+macro_rules! token_enum {
+    ($(#[$doc:meta])* $name:ident,
+     { $($(#[$vdoc:meta])* $variant:ident => $token:expr),+ }) => {
+        $(#[$doc])*
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($(#[$vdoc])* $variant),+
+        }
+
+        impl str::FromStr for $name {
+            type Err = ();
+            fn from_str(s: &str) -> Result<$name, ()> {
+                match s.trim() {
+                    $($token => Ok($name::$variant),)+
+                    _ => Err(()),
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str(match *self {
+                    $($name::$variant => $token),+
+                })
+            }
+        }
+    }
+}
+
+/// Defines a header holding a single token out of a fixed set. Unknown
+/// tokens fail to parse, so that the header is treated as absent, as
+/// recommended by the Fetch Metadata spec.
+macro_rules! token_header {
+    ($(#[$doc:meta])* $name:ident, $header:expr,
+     { $($(#[$vdoc:meta])* $variant:ident => $token:expr),+ }) => {
+        token_enum!($(#[$doc])* $name, { $($(#[$vdoc])* $variant => $token),+ });
+
+        #[cfg(feature = "hyper")]
+        impl Header for $name {
+            fn header_name() -> &'static str {
+                $header
+            }
+
+            fn parse_header(raw: &[Vec<u8>]) -> hyper::Result<$name> {
+                parsing::from_one_raw_str(raw)
+            }
+        }
+
+        #[cfg(feature = "hyper")]
+        impl HeaderFormat for $name {
+            fn fmt_header(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                fmt::Display::fmt(self, f)
+            }
+        }
+    }
+}
+
+token_header!(
+    /// The `Sec-Fetch-Site` header, telling how the initiator of a request
+    /// relates to its target.
+    /// See https://w3c.github.io/webappsec-fetch-metadata/#sec-fetch-site-header
+    ///
+    /// #Examples
+    #[cfg_attr(feature = "hyper", doc = "```")]
+    #[cfg_attr(not(feature = "hyper"), doc = "```ignore")]
+    /// extern crate hyper;
+    /// extern crate corsware;
+    /// use corsware::SecFetchSite;
+    /// use hyper::header::Headers;
+    /// # fn main() {
+    /// let mut headers = Headers::new();
+    /// headers.set_raw("Sec-Fetch-Site", vec![b"same-site".to_vec()]);
+    /// assert_eq!(headers.get::<SecFetchSite>(), Some(&SecFetchSite::SameSite));
+    /// # }
+    /// ```
+    SecFetchSite, "Sec-Fetch-Site", {
+        /// The request comes from another site
+        CrossSite => "cross-site",
+        /// The request comes from the same origin
+        SameOrigin => "same-origin",
+        /// The request comes from another origin on the same site
+        SameSite => "same-site",
+        /// The request was initiated by the user, such as by typing an URL
+        None => "none"
+    });
+
+token_header!(
+    /// The `Sec-Fetch-Mode` header, holding the mode of the request.
+    /// See https://w3c.github.io/webappsec-fetch-metadata/#sec-fetch-mode-header
+    SecFetchMode, "Sec-Fetch-Mode", {
+        /// A CORS request, such as `fetch()` to another origin
+        Cors => "cors",
+        /// A navigation between documents
+        Navigate => "navigate",
+        /// A request which doesn't use CORS, such as an image load
+        NoCors => "no-cors",
+        /// A request which may only target the same origin
+        SameOrigin => "same-origin",
+        /// A WebSocket handshake
+        Websocket => "websocket"
+    });
+
+token_header!(
+    /// The `Sec-Fetch-Dest` header, telling how the response will be used.
+    /// See https://w3c.github.io/webappsec-fetch-metadata/#sec-fetch-dest-header
+    SecFetchDest, "Sec-Fetch-Dest", {
+        /// Audio
+        Audio => "audio",
+        /// An audio worklet
+        AudioWorklet => "audioworklet",
+        /// A document, i.e. a top-level navigation
+        Document => "document",
+        /// An `<embed>` element
+        Embed => "embed",
+        /// No specific destination, such as `fetch()`
+        Empty => "empty",
+        /// A font
+        Font => "font",
+        /// A `<frame>` element
+        Frame => "frame",
+        /// An `<iframe>` element
+        Iframe => "iframe",
+        /// An image
+        Image => "image",
+        /// A web app manifest
+        Manifest => "manifest",
+        /// An `<object>` element
+        Object => "object",
+        /// A paint worklet
+        PaintWorklet => "paintworklet",
+        /// A report, such as a CSP violation report
+        Report => "report",
+        /// A script
+        Script => "script",
+        /// A service worker
+        ServiceWorker => "serviceworker",
+        /// A shared worker
+        SharedWorker => "sharedworker",
+        /// A style sheet
+        Style => "style",
+        /// A text track
+        Track => "track",
+        /// A video
+        Video => "video",
+        /// A dedicated worker
+        Worker => "worker",
+        /// An XSLT style sheet
+        Xslt => "xslt"
+    });
+
+/// The `Sec-Fetch-User` header, sent with navigations triggered by the user.
+/// See https://w3c.github.io/webappsec-fetch-metadata/#sec-fetch-user-header
+///
+/// #Examples
+#[cfg_attr(feature = "hyper", doc = "```")]
+#[cfg_attr(not(feature = "hyper"), doc = "```ignore")]
+/// extern crate hyper;
+/// extern crate corsware;
+/// use corsware::SecFetchUser;
+/// use hyper::header::Headers;
+/// # fn main() {
+/// let mut headers = Headers::new();
+/// headers.set_raw("Sec-Fetch-User", vec![b"?1".to_vec()]);
+/// assert_eq!(headers.get::<SecFetchUser>(), Some(&SecFetchUser(true)));
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SecFetchUser(pub bool);
+
+#[cfg(feature = "hyper")]
+impl Header for SecFetchUser {
+    fn header_name() -> &'static str {
+        "Sec-Fetch-User"
+    }
+
+    fn parse_header(raw: &[Vec<u8>]) -> hyper::Result<SecFetchUser> {
+        // A structured header boolean, '?1' or '?0'
+        match raw {
+            [value] if value == b"?1" => Ok(SecFetchUser(true)),
+            [value] if value == b"?0" => Ok(SecFetchUser(false)),
+            _ => Err(hyper::Error::Header),
+        }
+    }
+}
+
+#[cfg(feature = "hyper")]
+impl HeaderFormat for SecFetchUser {
+    fn fmt_header(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(if self.0 { "?1" } else { "?0" })
+    }
+}
+
+token_header!(
+    /// The `Cross-Origin-Resource-Policy` header, restricting which sites may
+    /// load a resource in `no-cors` mode, such as with `<img>` or `<script>`.
+    /// See https://fetch.spec.whatwg.org/#cross-origin-resource-policy-header
+    ///
+    /// #Examples
+    #[cfg_attr(feature = "hyper", doc = "```")]
+    #[cfg_attr(not(feature = "hyper"), doc = "```ignore")]
+    /// extern crate hyper;
+    /// extern crate corsware;
+    /// use corsware::CrossOriginResourcePolicy;
+    /// use hyper::header::Headers;
+    /// # fn main() {
+    /// let mut headers = Headers::new();
+    /// headers.set(CrossOriginResourcePolicy::SameSite);
+    /// assert_eq!(headers.get_raw("Cross-Origin-Resource-Policy"),
+    ///            Some(&[b"same-site".to_vec()][..]));
+    /// # }
+    /// ```
+    CrossOriginResourcePolicy, "Cross-Origin-Resource-Policy", {
+        /// Only the same origin may load the resource
+        SameOrigin => "same-origin",
+        /// Only the same site may load the resource
+        SameSite => "same-site",
+        /// Any origin may load the resource
+        CrossOrigin => "cross-origin"
+    });
+
+token_enum!(
+    /// The value of a `Cross-Origin-Opener-Policy` header.
+    /// See https://html.spec.whatwg.org/multipage/browsers.html#cross-origin-opener-policies
+    OpenerPolicy, {
+        /// Don't isolate the browsing context
+        UnsafeNone => "unsafe-none",
+        /// Only share the browsing context group with same-origin documents
+        SameOrigin => "same-origin",
+        /// Like `SameOrigin`, but keep references to popups opened
+        SameOriginAllowPopups => "same-origin-allow-popups",
+        /// Always put the document in a new browsing context group, but
+        /// keep references to popups opened
+        NoopenerAllowPopups => "noopener-allow-popups"
+    });
+
+token_enum!(
+    /// The value of a `Cross-Origin-Embedder-Policy` header.
+    /// See https://html.spec.whatwg.org/multipage/browsers.html#coep
+    EmbedderPolicy, {
+        /// Allow loading cross-origin resources without their permission
+        UnsafeNone => "unsafe-none",
+        /// Only load cross-origin resources allowed by CORS or CORP
+        RequireCorp => "require-corp",
+        /// Load cross-origin `no-cors` resources without credentials
+        Credentialless => "credentialless"
+    });
+
+/// Defines a header holding a policy token and an optional `report-to`
+/// parameter, such as `same-origin; report-to="default"`.
+macro_rules! policy_header {
+    ($(#[$doc:meta])* $name:ident, $header:expr, $policy:ident) => {
+        $(#[$doc])*
+        #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+        pub struct $name {
+            /// The policy
+            pub policy: $policy,
+            /// The name of the Reporting API endpoint to send violations to
+            pub report_to: Option<String>,
+        }
+
+        impl $name {
+            /// New header with the given policy, without reporting
+            pub fn new(policy: $policy) -> $name {
+                $name {
+                    policy,
+                    report_to: None,
+                }
+            }
+        }
+
+        #[cfg(feature = "hyper")]
+        impl Header for $name {
+            fn header_name() -> &'static str {
+                $header
+            }
+
+            fn parse_header(raw: &[Vec<u8>]) -> hyper::Result<$name> {
+                let value: String = parsing::from_one_raw_str(raw)?;
+                let (policy, report_to) = parse_policy(&value).ok_or(hyper::Error::Header)?;
+                Ok($name {
+                    policy: policy.parse().map_err(|_| hyper::Error::Header)?,
+                    report_to,
+                })
+            }
+        }
+
+        #[cfg(feature = "hyper")]
+        impl HeaderFormat for $name {
+            fn fmt_header(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                fmt::Display::fmt(self, f)
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}", self.policy)?;
+                match self.report_to {
+                    Some(ref endpoint) => write!(f, "; report-to=\"{}\"", endpoint),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+/// Splits a structured header token with parameters into the token and
+/// the value of its `report-to` parameter. Other parameters are ignored.
+#[cfg(feature = "hyper")]
+fn parse_policy(value: &str) -> Option<(&str, Option<String>)> {
+    let mut parts = value.split(';').map(|p| p.trim());
+    let policy = parts.next().filter(|p| !p.is_empty())?;
+    let mut report_to = None;
+    for param in parts {
+        let mut kv = param.splitn(2, '=');
+        if kv.next() == Some("report-to") {
+            let endpoint = kv.next()?;
+            if endpoint.len() < 2 || !endpoint.starts_with('"') || !endpoint.ends_with('"') {
+                return None;
+            }
+            report_to = Some(endpoint[1..endpoint.len() - 1].to_owned());
+        }
+    }
+    Some((policy, report_to))
+}
+
+policy_header!(
+    /// The `Cross-Origin-Opener-Policy` header.
+    ///
+    /// #Examples
+    #[cfg_attr(feature = "hyper", doc = "```")]
+    #[cfg_attr(not(feature = "hyper"), doc = "```ignore")]
+    /// extern crate hyper;
+    /// extern crate corsware;
+    /// use corsware::{CrossOriginOpenerPolicy, OpenerPolicy};
+    /// use hyper::header::Headers;
+    /// # fn main() {
+    /// let mut headers = Headers::new();
+    /// headers.set_raw("Cross-Origin-Opener-Policy",
+    ///                 vec![b"same-origin; report-to=\"coop\"".to_vec()]);
+    /// assert_eq!(headers.get::<CrossOriginOpenerPolicy>(),
+    ///            Some(&CrossOriginOpenerPolicy {
+    ///                policy: OpenerPolicy::SameOrigin,
+    ///                report_to: Some("coop".to_owned()),
+    ///            }));
+    /// # }
+    /// ```
+    CrossOriginOpenerPolicy, "Cross-Origin-Opener-Policy", OpenerPolicy);
+
+policy_header!(
+    /// The `Cross-Origin-Opener-Policy-Report-Only` header, reporting
+    /// violations of the policy without enforcing it.
+    CrossOriginOpenerPolicyReportOnly, "Cross-Origin-Opener-Policy-Report-Only", OpenerPolicy);
+
+policy_header!(
+    /// The `Cross-Origin-Embedder-Policy` header.
+    CrossOriginEmbedderPolicy, "Cross-Origin-Embedder-Policy", EmbedderPolicy);
+
+policy_header!(
+    /// The `Cross-Origin-Embedder-Policy-Report-Only` header, reporting
+    /// violations of the policy without enforcing it.
+    CrossOriginEmbedderPolicyReportOnly, "Cross-Origin-Embedder-Policy-Report-Only",
+    EmbedderPolicy);
+
+/// The `Timing-Allow-Origin` header, allowing origins to see detailed
+/// resource timing information.
+/// See https://w3c.github.io/resource-timing/#sec-timing-allow-origin
+///
+/// #Examples
+#[cfg_attr(feature = "hyper", doc = "```")]
+#[cfg_attr(not(feature = "hyper"), doc = "```ignore")]
+/// extern crate hyper;
+/// extern crate corsware;
+/// use corsware::TimingAllowOrigin;
+/// use hyper::header::Headers;
+/// # fn main() {
+/// let mut headers = Headers::new();
+/// headers.set_raw("Timing-Allow-Origin", vec![b"*".to_vec()]);
+/// assert_eq!(headers.get::<TimingAllowOrigin>(), Some(&TimingAllowOrigin::Any));
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TimingAllowOrigin {
+    /// Any origin, `*`
+    Any,
+    /// The given origins
+    Value(Vec<String>),
+}
+
+#[cfg(feature = "hyper")]
+impl Header for TimingAllowOrigin {
+    fn header_name() -> &'static str {
+        "Timing-Allow-Origin"
+    }
+
+    fn parse_header(raw: &[Vec<u8>]) -> hyper::Result<TimingAllowOrigin> {
+        let values: Vec<String> = parsing::from_comma_delimited(raw)?;
+        if values.is_empty() {
+            Err(hyper::Error::Header)
+        } else if values.iter().any(|v| v == "*") {
+            Ok(TimingAllowOrigin::Any)
+        } else {
+            Ok(TimingAllowOrigin::Value(values))
+        }
+    }
+}
+
+#[cfg(feature = "hyper")]
+impl HeaderFormat for TimingAllowOrigin {
+    fn fmt_header(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Display for TimingAllowOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TimingAllowOrigin::Any => f.write_str("*"),
+            TimingAllowOrigin::Value(ref values) => f.write_str(&values.join(", ")),
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Cross-origin isolation headers, see `CrossOriginIsolation`.
+
+use iron::prelude::*;
+use iron::middleware::AfterMiddleware;
+use headers::{CrossOriginOpenerPolicy, CrossOriginOpenerPolicyReportOnly,
+              CrossOriginEmbedderPolicy, CrossOriginEmbedderPolicyReportOnly, OpenerPolicy,
+              EmbedderPolicy};
+
+/// Sets the `Cross-Origin-Opener-Policy` and `Cross-Origin-Embedder-Policy`
+/// headers, and their report-only variants, on all responses. Together they
+/// make a document cross-origin isolated, which is required for features such
+/// as `SharedArrayBuffer`. Headers already set by the handler are left as is.
+///
+/// With `require-corp`, every cross-origin resource the document loads has to be
+/// allowed by CORS or by `Cross-Origin-Resource-Policy`, see
+/// `CorsMiddleware::resource_policy`.
+///
+/// #Example
+/// ```
+/// extern crate iron;
+/// extern crate corsware;
+/// use corsware::CrossOriginIsolation;
+/// use iron::prelude::*;
+/// use iron::status;
+///
+/// fn main() {
+///   let handler = |_: &mut Request| {
+///       Ok(Response::with((status::Ok, "Hello world!")))
+///   };
+///   let mut chain = Chain::new(handler);
+///   chain.link_after(CrossOriginIsolation::report_only(Some("default".to_owned())));
+///   let mut listening = Iron::new(chain).http("localhost:0").unwrap();
+///   listening.close().unwrap();
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct CrossOriginIsolation {
+    /// The enforced opener policy
+    pub opener_policy: Option<CrossOriginOpenerPolicy>,
+    /// The enforced embedder policy
+    pub embedder_policy: Option<CrossOriginEmbedderPolicy>,
+    /// An opener policy which is only reported on
+    pub opener_policy_report_only: Option<CrossOriginOpenerPolicyReportOnly>,
+    /// An embedder policy which is only reported on
+    pub embedder_policy_report_only: Option<CrossOriginEmbedderPolicyReportOnly>,
+}
+
+impl CrossOriginIsolation {
+    /// Enforces cross-origin isolation, with
+    /// `Cross-Origin-Opener-Policy: same-origin` and
+    /// `Cross-Origin-Embedder-Policy: require-corp`. Violations are reported
+    /// to the given Reporting API endpoint, if any.
+    pub fn enforced(report_to: Option<String>) -> CrossOriginIsolation {
+        CrossOriginIsolation {
+            opener_policy: Some(CrossOriginOpenerPolicy {
+                policy: OpenerPolicy::SameOrigin,
+                report_to: report_to.clone(),
+            }),
+            embedder_policy: Some(CrossOriginEmbedderPolicy {
+                policy: EmbedderPolicy::RequireCorp,
+                report_to,
+            }),
+            ..CrossOriginIsolation::default()
+        }
+    }
+
+    /// Reports what would break under cross-origin isolation, without
+    /// enforcing it. Useful for trying out the policies before enabling them.
+    pub fn report_only(report_to: Option<String>) -> CrossOriginIsolation {
+        CrossOriginIsolation {
+            opener_policy_report_only: Some(CrossOriginOpenerPolicyReportOnly {
+                policy: OpenerPolicy::SameOrigin,
+                report_to: report_to.clone(),
+            }),
+            embedder_policy_report_only: Some(CrossOriginEmbedderPolicyReportOnly {
+                policy: EmbedderPolicy::RequireCorp,
+                report_to,
+            }),
+            ..CrossOriginIsolation::default()
+        }
+    }
+
+    fn decorate(&self, res: &mut Response) {
+        if let Some(ref policy) = self.opener_policy {
+            if !res.headers.has::<CrossOriginOpenerPolicy>() {
+                res.headers.set(policy.clone());
+            }
+        }
+        if let Some(ref policy) = self.embedder_policy {
+            if !res.headers.has::<CrossOriginEmbedderPolicy>() {
+                res.headers.set(policy.clone());
+            }
+        }
+        if let Some(ref policy) = self.opener_policy_report_only {
+            if !res.headers.has::<CrossOriginOpenerPolicyReportOnly>() {
+                res.headers.set(policy.clone());
+            }
+        }
+        if let Some(ref policy) = self.embedder_policy_report_only {
+            if !res.headers.has::<CrossOriginEmbedderPolicyReportOnly>() {
+                res.headers.set(policy.clone());
+            }
+        }
+    }
+}
+
+impl AfterMiddleware for CrossOriginIsolation {
+    fn after(&self, _: &mut Request, mut res: Response) -> IronResult<Response> {
+        self.decorate(&mut res);
+        Ok(res)
+    }
+
+    fn catch(&self, _: &mut Request, mut err: IronError) -> IronResult<Response> {
+        self.decorate(&mut err.response);
+        Err(err)
+    }
+}
@@ -0,0 +1,248 @@
+//! A tower layer applying `CorsMiddleware`, enabled by the `tower` feature.
+
+use http::{HeaderMap, HeaderValue, Request, Response, StatusCode};
+use http::header::{HeaderName, CONTENT_TYPE, VARY};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower_layer::Layer;
+use tower_service::Service;
+use evaluate::{CorsRequest, CorsOutcome, CorsHeaders, is_websocket_handshake,
+               sets_response_header};
+use CorsMiddleware;
+
+/// A tower layer applying the policy of a `CorsMiddleware` to a service, with
+/// the same semantics as the Iron middleware. Preflight requests and rejected
+/// requests are answered without calling the service, and responses to normal
+/// requests are decorated with CORS headers.
+///
+/// `Vary` is merged with any `Vary` header set by the service, and a
+/// `Cross-Origin-Resource-Policy` set by the service is kept. Observers aren't
+/// notified, since they take Iron requests.
+///
+/// The response body type has to be constructible from a `String`, for the
+/// bodies of preflight responses and rejections.
+///
+/// #Example
+/// ```
+/// extern crate corsware;
+/// extern crate futures_executor;
+/// extern crate http;
+/// extern crate tower;
+/// use corsware::{CorsMiddleware, CorsLayer};
+/// use http::{Request, Response};
+/// use std::convert::Infallible;
+/// use std::future;
+/// use tower::{Layer, ServiceExt, service_fn};
+///
+/// fn main() {
+///     let service = CorsLayer::new(CorsMiddleware::permissive()).layer(service_fn(|_| {
+///         future::ready(Ok::<_, Infallible>(Response::new("Hello world!".to_owned())))
+///     }));
+///     let request = Request::builder()
+///         .header("Origin", "http://a.com")
+///         .body(())
+///         .unwrap();
+///     let response = futures_executor::block_on(service.oneshot(request)).unwrap();
+///     assert_eq!(response.headers()["Access-Control-Allow-Origin"], "http://a.com");
+/// }
+/// ```
+#[derive(Clone)]
+pub struct CorsLayer {
+    cors: Arc<CorsMiddleware>,
+}
+
+impl CorsLayer {
+    /// New layer applying the policy of the given middleware
+    pub fn new(cors: CorsMiddleware) -> CorsLayer {
+        CorsLayer { cors: Arc::new(cors) }
+    }
+}
+
+impl<S> Layer<S> for CorsLayer {
+    type Service = CorsService<S>;
+
+    fn layer(&self, inner: S) -> CorsService<S> {
+        CorsService {
+            inner,
+            cors: self.cors.clone(),
+        }
+    }
+}
+
+/// A service wrapped by a `CorsLayer`
+#[derive(Clone)]
+pub struct CorsService<S> {
+    inner: S,
+    cors: Arc<CorsMiddleware>,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for CorsService<S>
+    where S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+          ResBody: From<String>
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = CorsResponseFuture<S::Future, ResBody>;
+
+    fn poll_ready(&mut self, cx: &mut Context) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        let cors_req = CorsRequest::from_http(&req);
+        if self.cors.guard_websockets && is_websocket_handshake(req.headers()) {
+            // No CORS headers are added to handshake responses
+            return match self.cors.check_websocket(&cors_req) {
+                Ok(()) => CorsResponseFuture::inner(self.inner.call(req), None, vec![]),
+                Err(message) => {
+                    CorsResponseFuture::ready(response(StatusCode::FORBIDDEN, message, None))
+                }
+            };
+        }
+        let common = self.cors.response_headers(&cors_req);
+        match self.cors.process(&cors_req, req.uri().path()) {
+            CorsOutcome::NotCors => CorsResponseFuture::inner(self.inner.call(req), None, common),
+            CorsOutcome::Normal(headers) => {
+                CorsResponseFuture::inner(self.inner.call(req), Some(headers), common)
+            }
+            CorsOutcome::Preflight(headers) => {
+                // Successful preflight status code is NoContent
+                let mut res = response(StatusCode::NO_CONTENT, String::new(), None);
+                decorate(&mut res, Some(headers), &common);
+                CorsResponseFuture::ready(res)
+            }
+            CorsOutcome::Rejected(rejection) => {
+                let (body, content_type) = self.cors.rejection_body(&cors_req, &rejection);
+                let mut res = response(StatusCode::BAD_REQUEST, body, Some(content_type));
+                decorate(&mut res, None, &common);
+                CorsResponseFuture::ready(res)
+            }
+        }
+    }
+}
+
+/// The future returned by `CorsService`
+pub struct CorsResponseFuture<F, B> {
+    kind: Kind<F, B>,
+}
+
+enum Kind<F, B> {
+    /// The response was decided without calling the service
+    Ready(Option<Response<B>>),
+    /// The response of the service, to be decorated with the given headers
+    Inner {
+        future: Pin<Box<F>>,
+        headers: Option<CorsHeaders>,
+        common: Vec<(String, String)>,
+    },
+}
+
+impl<F, B> CorsResponseFuture<F, B> {
+    fn ready(res: Response<B>) -> CorsResponseFuture<F, B> {
+        CorsResponseFuture { kind: Kind::Ready(Some(res)) }
+    }
+
+    fn inner(future: F,
+             headers: Option<CorsHeaders>,
+             common: Vec<(String, String)>)
+             -> CorsResponseFuture<F, B> {
+        CorsResponseFuture {
+            kind: Kind::Inner {
+                future: Box::pin(future),
+                headers,
+                common,
+            },
+        }
+    }
+}
+
+// The inner future is boxed, and the response is never pinned
+impl<F, B> Unpin for CorsResponseFuture<F, B> {}
+
+impl<F, B, E> Future for CorsResponseFuture<F, B>
+    where F: Future<Output = Result<Response<B>, E>>
+{
+    type Output = Result<Response<B>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        match self.get_mut().kind {
+            Kind::Ready(ref mut res) => {
+                Poll::Ready(Ok(res.take().expect("future polled after completion")))
+            }
+            Kind::Inner { ref mut future, ref mut headers, ref common } => {
+                match future.as_mut().poll(cx) {
+                    Poll::Pending => Poll::Pending,
+                    Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+                    Poll::Ready(Ok(mut res)) => {
+                        decorate(&mut res, headers.take(), common);
+                        Poll::Ready(Ok(res))
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn response<B: From<String>>(status: StatusCode,
+                             body: String,
+                             content_type: Option<&'static str>)
+                             -> Response<B> {
+    let mut res = Response::new(B::from(body));
+    *res.status_mut() = status;
+    if let Some(content_type) = content_type {
+        res.headers_mut().insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+    }
+    res
+}
+
+/// Adds the CORS headers of an allowed request, if any, and the headers set
+/// on every response, to a response
+fn decorate<B>(res: &mut Response<B>, headers: Option<CorsHeaders>, common: &[(String, String)]) {
+    let map = res.headers_mut();
+    if let Some(headers) = headers {
+        for (name, value) in headers.to_header_map() {
+            if let Some(name) = name {
+                map.insert(name, value);
+            }
+        }
+    }
+    for (name, value) in common {
+        let name = match HeaderName::from_bytes(name.as_bytes()) {
+            Ok(name) => name,
+            Err(_) => continue,
+        };
+        if name == VARY {
+            merge_vary(map, value);
+        } else if !sets_response_header(name.as_str(),
+                                         map.contains_key("cross-origin-resource-policy")) {
+            continue;
+        } else if let Ok(value) = HeaderValue::from_str(value) {
+            map.insert(name, value);
+        }
+    }
+}
+
+/// Adds the given comma-separated header names to `Vary`, keeping the names
+/// already listed. `Vary: *` is kept as is.
+fn merge_vary(map: &mut HeaderMap, value: &str) {
+    let mut names: Vec<String> = map.get_all(VARY)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|n| n.trim().to_owned())
+        .filter(|n| !n.is_empty())
+        .collect();
+    if names.iter().any(|n| n == "*") {
+        return;
+    }
+    for name in value.split(',').map(|n| n.trim()) {
+        if !names.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+            names.push(name.to_owned());
+        }
+    }
+    if let Ok(value) = HeaderValue::from_str(&names.join(", ")) {
+        map.insert(VARY, value);
+    }
+}
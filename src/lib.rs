@@ -1,33 +1,100 @@
 //! # Corsware
 //! Yet another implementation of the CORS Specification for Iron.
+//!
+//! The policy itself is independent of Iron, see `CorsMiddleware::evaluate()`.
+//! The Iron middlewares are enabled by the default `iron` feature, a plain
+//! hyper 0.10 handler by the `hyper` feature and a tower layer by the `tower`
+//! feature.
 
+#[cfg(feature = "iron")]
 extern crate iron;
+#[cfg(feature = "iron")]
+extern crate mount;
+#[cfg(feature = "hyper")]
+extern crate hyper;
+extern crate http;
 extern crate unicase;
+#[cfg(feature = "tower")]
+extern crate tower_layer;
+#[cfg(feature = "tower")]
+extern crate tower_service;
+#[cfg(feature = "log")]
 #[macro_use]
-extern crate hyper;
+extern crate log;
 
 pub use unicase::UniCase;
-use iron::prelude::*;
-use iron::method::Method;
-use iron::method::Method::*;
-use iron::status;
-//use iron::headers::Origin as OriginHeader;
-use iron::headers::{AccessControlRequestMethod, AccessControlRequestHeaders,
-                    AccessControlAllowOrigin, AccessControlAllowHeaders, AccessControlMaxAge,
-                    AccessControlAllowMethods, AccessControlAllowCredentials,
-                    AccessControlExposeHeaders, Vary};
-use iron::middleware::{AroundMiddleware, Handler};
+use http::Method;
 use std::collections::HashSet;
-use std::iter::FromIterator;
-pub use origin::Origin;
+use std::net::IpAddr;
+use std::sync::Arc;
+pub use origin::{Origin, Host, CidrBlock, OpaqueId, ParseError, SyntaxError};
+pub use headers::{OriginHeader, OriginList, SecFetchSite, SecFetchMode, SecFetchDest, SecFetchUser,
+                  CrossOriginResourcePolicy, CrossOriginOpenerPolicy,
+                  CrossOriginOpenerPolicyReportOnly, CrossOriginEmbedderPolicy,
+                  CrossOriginEmbedderPolicyReportOnly, OpenerPolicy, EmbedderPolicy,
+                  TimingAllowOrigin};
+#[cfg(feature = "iron")]
+pub use csrf::CsrfOriginGuard;
+#[cfg(feature = "iron")]
+pub use fetch_metadata::ResourceIsolationPolicy;
+#[cfg(feature = "iron")]
+pub use isolation::CrossOriginIsolation;
+pub use reporting::{CandidatePolicy, Violation, ViolationKind, ViolationSink, ViolationReport,
+                    ViolationReporter, JsonLinesReporter, RingBufferReporter};
+#[cfg(feature = "hyper")]
+pub use reporting::HttpReporter;
+pub use observer::{CorsObserver, CorsDecision};
+pub use metrics::CorsMetrics;
+#[cfg(feature = "iron")]
+pub use metrics::MetricsHandler;
+pub use debug::CorsDebug;
+pub use evaluate::{CorsRequest, CorsOutcome, CorsHeaders, CorsRejection};
+#[cfg(feature = "hyper")]
+pub use handler::CorsHandler;
+#[cfg(feature = "tower")]
+pub use layer::{CorsLayer, CorsService, CorsResponseFuture};
+
+// Logging is a no-op without the log feature. The arguments are still
+// type-checked, so that the build doesn't break or warn either way.
+#[cfg(feature = "log")]
+macro_rules! log_info {
+    ($($arg:tt)*) => { info!($($arg)*) }
+}
+#[cfg(feature = "log")]
+macro_rules! log_debug {
+    ($($arg:tt)*) => { debug!($($arg)*) }
+}
+#[cfg(not(feature = "log"))]
+macro_rules! log_info {
+    ($($arg:tt)*) => { if false { let _ = format!($($arg)*); } }
+}
+#[cfg(not(feature = "log"))]
+macro_rules! log_debug {
+    ($($arg:tt)*) => { if false { let _ = format!($($arg)*); } }
+}
 
 mod origin;
+mod headers;
+#[cfg(feature = "iron")]
+mod exempt;
+#[cfg(feature = "iron")]
+mod csrf;
+#[cfg(feature = "iron")]
+mod fetch_metadata;
+#[cfg(feature = "iron")]
+mod isolation;
+mod reporting;
+mod observer;
+mod metrics;
+mod debug;
+mod evaluate;
+#[cfg(feature = "hyper")]
+mod handler;
+#[cfg(feature = "iron")]
+mod middleware;
+#[cfg(feature = "tower")]
+mod layer;
 
-header! {
-    /// A Custom Origin header which allows for null origins, something the standard
-    /// Iron header does not.
-    (OriginHeader, "Origin") => [String]
-}
 
 /// Specifies which origins are allowed to access this resource
 #[derive(Clone)]
@@ -39,10 +106,71 @@ pub enum AllowedOrigins {
         /// https://tools.ietf.org/id/draft-abarth-origin-03.html#rfc.section.6
         /// https://w3c.github.io/webappsec-cors-for-developers/
         allow_null: bool,
+        /// Allowing opaque origins is a separate setting as well. These are
+        /// sent by browsers as null, so in practice only non-browser clients
+        /// could send an Origin header with an opaque origin, such as `data:...`.
+        /// Such values don't follow the grammar of the Origin header, and are
+        /// rejected as malformed unless opaque origins are allowed.
+        allow_opaque: bool,
     },
     /// Allow a specific set of origins. Remember that allowing
-    /// for a null header is risky.
+    /// for a null header is risky. Opaque origins are never part of the set,
+    /// since they aren't equal to any other origin.
     Specific(HashSet<Origin>),
+    /// Allow all origins matched by any of the rules.
+    Rules(Vec<OriginRule>),
+}
+
+/// A rule matching a group of origins, see `AllowedOrigins::Rules`.
+///
+/// The set of rules depends on the enabled features, so matches on
+/// `OriginRule` need a wildcard arm.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum OriginRule {
+    /// Matches the given origin only. Use `Exact(Origin::Null)` to allow null origins.
+    Exact(Origin),
+    /// Matches all opaque origins, see `Origin::Opaque`. Like with `allow_opaque`
+    /// of `AllowedOrigins::Any`, Origin headers with opaque origins are only
+    /// accepted if the rules include this rule.
+    AnyOpaque,
+    /// Matches origins whose host is an IP address within the given block,
+    /// regardless of scheme and port.
+    IpRange(CidrBlock),
+    /// Matches origins whose registrable domain (eTLD+1) is the given domain,
+    /// regardless of scheme and port. The domain must be lowercase and punycoded,
+    /// such as `example.co.uk`. See `Origin::registrable_domain()`.
+    RegistrableDomain(String),
+    /// Matches `http` and `https` origins on the loopback interface, i.e. `localhost`,
+    /// `127.0.0.0/8` and `[::1]`, regardless of port. Meant for local development
+    /// servers, and therefore only available with the `dev-mode` feature.
+    #[cfg(feature = "dev-mode")]
+    LoopbackAnyPort,
+}
+
+impl OriginRule {
+    /// Returns true if the given origin is matched by this rule.
+    pub fn matches(&self, origin: &Origin) -> bool {
+        match *self {
+            OriginRule::Exact(ref exact) => exact == origin,
+            OriginRule::AnyOpaque => origin.is_opaque(),
+            OriginRule::IpRange(ref block) => {
+                match origin.typed_host() {
+                    Some(Host::Ipv4(ip)) => block.contains(&IpAddr::V4(ip)),
+                    Some(Host::Ipv6(ip)) => block.contains(&IpAddr::V6(ip)),
+                    Some(Host::Domain(_)) | None => false,
+                }
+            }
+            OriginRule::RegistrableDomain(ref domain) => {
+                origin.registrable_domain().as_ref() == Some(domain)
+            }
+            #[cfg(feature = "dev-mode")]
+            OriginRule::LoopbackAnyPort => {
+                // Loopback origins are always triples
+                origin.is_loopback() && (origin.scheme() == "http" || origin.scheme() == "https")
+            }
+        }
+    }
 }
 
 impl AllowedOrigins {
@@ -80,14 +208,15 @@ impl AllowedOrigins {
                        allow_credentials: bool,
                        prefer_wildcard: bool)
                        -> Option<String> {
-        match Origin::parse_allow_null(origin_string) {
+        match Origin::parse_allow_opaque(origin_string) {
             Err(_) => None,
             Ok(origin) => {
                 match *self {
-                    AllowedOrigins::Any { allow_null } => {
-                        // Any origin is allowed, but this does not include Null,
-                        // special check for that
-                        if origin == Origin::Null && !allow_null {
+                    AllowedOrigins::Any { allow_null, allow_opaque } => {
+                        // Any origin is allowed, but this does not include Null
+                        // or opaque origins, special check for those
+                        if (origin == Origin::Null && !allow_null) ||
+                           (origin.is_opaque() && !allow_opaque) {
                             None
                         } else {
                             self.allow(origin_string, prefer_wildcard, allow_credentials)
@@ -100,14 +229,156 @@ impl AllowedOrigins {
                             None
                         }
                     }
+                    AllowedOrigins::Rules(ref rules) => {
+                        if rules.iter().any(|rule| rule.matches(&origin)) {
+                            self.allow(origin_string, prefer_wildcard, allow_credentials)
+                        } else {
+                            None
+                        }
+                    }
                 }
             }
         }
 
     }
+
+    /// Returns true if opaque origins may be allowed by this policy
+    fn allows_opaque(&self) -> bool {
+        match *self {
+            AllowedOrigins::Any { allow_opaque, .. } => allow_opaque,
+            AllowedOrigins::Specific(_) => false,
+            AllowedOrigins::Rules(ref rules) => {
+                rules.iter().any(|rule| matches!(*rule, OriginRule::AnyOpaque))
+            }
+        }
+    }
+
+    /// Returns the `Cross-Origin-Resource-Policy` which lets the allowed
+    /// origins load resources in `no-cors` mode, see
+    /// `ResourcePolicy::FromAllowedOrigins`
+    fn resource_policy(&self) -> CrossOriginResourcePolicy {
+        let listed: Vec<&Origin> = match *self {
+            AllowedOrigins::Any { .. } => return CrossOriginResourcePolicy::CrossOrigin,
+            AllowedOrigins::Specific(ref origins) => origins.iter().collect(),
+            AllowedOrigins::Rules(ref rules) => {
+                let mut listed = vec![];
+                for rule in rules {
+                    match *rule {
+                        OriginRule::Exact(ref origin) => listed.push(origin),
+                        // Other rules may match origins of any site
+                        _ => return CrossOriginResourcePolicy::CrossOrigin,
+                    }
+                }
+                listed
+            }
+        };
+        match listed.split_first() {
+            None => CrossOriginResourcePolicy::SameSite,
+            Some((first, rest)) if first.is_tuple() &&
+                                   rest.iter().all(|o| first.is_same_site(o)) => {
+                CrossOriginResourcePolicy::SameSite
+            }
+            Some(_) => CrossOriginResourcePolicy::CrossOrigin,
+        }
+    }
+
+    /// Describes which part of this policy allows the given origin, or why
+    /// the origin isn't allowed
+    fn explain(&self, origin_string: &str) -> String {
+        let origin = match Origin::parse_allow_opaque(origin_string) {
+            Ok(origin) => origin,
+            Err(e) => return format!("unparseable origin: {}", e),
+        };
+        match *self {
+            AllowedOrigins::Any { allow_null, allow_opaque } => {
+                if origin == Origin::Null && !allow_null {
+                    "null origin not allowed".to_owned()
+                } else if origin.is_opaque() && !allow_opaque {
+                    "opaque origin not allowed".to_owned()
+                } else {
+                    "any origin allowed".to_owned()
+                }
+            }
+            AllowedOrigins::Specific(ref allowed) => {
+                if allowed.contains(&origin) {
+                    format!("listed origin {}", origin)
+                } else {
+                    "origin not listed".to_owned()
+                }
+            }
+            AllowedOrigins::Rules(ref rules) => {
+                match rules.iter().find(|rule| rule.matches(&origin)) {
+                    Some(rule) => format!("rule {:?}", rule),
+                    None => "no rule matched".to_owned(),
+                }
+            }
+        }
+    }
 }
 
-/// An Iron middleware implementing CORS.
+/// Specifies which of the allowed origins are granted credentials when
+/// `allow_credentials` is set.
+#[derive(Clone)]
+pub enum CredentialsPolicy {
+    /// Credentials are granted to every allowed origin.
+    AnyOrigin,
+    /// Credentials are only granted to secure origins, i.e. origins using the
+    /// `https` or `wss` scheme. Cookies sent to an insecure origin can be read by
+    /// anyone able to impersonate that origin on the network.
+    SecureOrigins {
+        /// Insecure origins which are granted credentials anyway, typically local
+        /// development servers. Only origins referring to the loopback interface are
+        /// considered, any other origin in this set is ignored.
+        loopback: HashSet<Origin>,
+        /// If set, requests from insecure origins are rejected. If not set, they
+        /// are served as if credentials were not allowed.
+        reject_insecure: bool,
+    },
+}
+
+/// Specifies the `Cross-Origin-Resource-Policy` set on responses, restricting
+/// which origins may load the resource in `no-cors` mode, such as with `<img>`
+/// or `<script>`. CORS requests aren't affected by this policy.
+#[derive(Clone)]
+pub enum ResourcePolicy {
+    /// No `Cross-Origin-Resource-Policy` header is set.
+    Omit,
+    /// Derived from the allowed origins, so that they can still load the
+    /// resource in `no-cors` mode: `same-site` if all allowed origins are
+    /// listed, either as `Specific` origins or as `Exact` rules, and belong to
+    /// the same site, `cross-origin` otherwise.
+    ///
+    /// The middleware doesn't know the origin it's served from, so
+    /// `same-site` assumes that the resource is served from the site of the
+    /// allowed origins. If it isn't, the allowed origins have to use CORS to
+    /// load it. On the other hand, `cross-origin` lets any site embed the
+    /// resource, so use `Fixed` where a stricter policy is wanted.
+    FromAllowedOrigins,
+    /// The given policy is set.
+    Fixed(CrossOriginResourcePolicy),
+}
+
+/// Specifies when `Timing-Allow-Origin` is set on responses to normal CORS
+/// requests, letting the origin see detailed resource timing information.
+#[derive(Clone)]
+pub enum TimingPolicy {
+    /// No `Timing-Allow-Origin` header is set.
+    Omit,
+    /// Set to the same value as `Access-Control-Allow-Origin`, i.e. the origin
+    /// or `*`.
+    SameAsCors,
+    /// Set for origins allowed by the given list, independently of which origins
+    /// are allowed by CORS. Responses to requests rejected by CORS don't get the
+    /// header. Uses `*` for `AllowedOrigins::Any` if `prefer_wildcard` is set.
+    Independent(AllowedOrigins),
+}
+
+/// An Iron middleware implementing CORS. Without the `iron` feature, the
+/// policy is applied with `CorsMiddleware::evaluate()`.
+///
+/// Requests carrying more than one distinct origin, either as repeated Origin
+/// headers or as a space-separated list, are rejected with `400 Bad Request`.
+/// Repeated identical origins are treated as a single origin. See `OriginList`.
 ///
 /// Note: Not using `Vec<Header>` to represent
 /// headers since the Iron `Header`
@@ -119,7 +390,8 @@ impl AllowedOrigins {
 /// strings to represent this.
 ///
 /// # Simple Example
-/// ```
+#[cfg_attr(feature = "iron", doc = "```")]
+#[cfg_attr(not(feature = "iron"), doc = "```ignore")]
 /// extern crate iron;
 /// extern crate corsware;
 /// use corsware::CorsMiddleware;
@@ -137,11 +409,14 @@ impl AllowedOrigins {
 /// }
 /// ```
 /// # A More Elaborate Example
-/// ```
+#[cfg_attr(feature = "iron", doc = "```")]
+#[cfg_attr(not(feature = "iron"), doc = "```ignore")]
 /// extern crate iron;
+/// extern crate http;
 /// extern crate corsware;
-/// use corsware::{CorsMiddleware, AllowedOrigins, UniCase};
-/// use iron::method::Method::{Get,Post};
+/// use corsware::{CorsMiddleware, AllowedOrigins, CredentialsPolicy, ResourcePolicy,
+///                 TimingPolicy, UniCase};
+/// use http::Method;
 /// use iron::prelude::*;
 /// use iron::status;
 ///
@@ -150,13 +425,21 @@ impl AllowedOrigins {
 ///       Ok(Response::with((status::Ok, "Hello world!")))
 ///   };
 ///   let cors = CorsMiddleware {
-///     allowed_origins : AllowedOrigins::Any { allow_null: false },
+///     allowed_origins : AllowedOrigins::Any { allow_null: false, allow_opaque: false },
 ///     allowed_headers: vec![UniCase("Content-Type".to_owned())],
-///     allowed_methods : vec![ Get, Post ],
+///     allowed_methods : vec![ Method::GET, Method::POST ],
 ///     exposed_headers: vec![],
 ///     allow_credentials: false,
+///     credentials_policy: CredentialsPolicy::AnyOrigin,
 ///     max_age_seconds: 60 * 60,
-///     prefer_wildcard: true
+///     prefer_wildcard: true,
+///     guard_websockets: false,
+///     resource_policy: ResourcePolicy::FromAllowedOrigins,
+///     timing_policy: TimingPolicy::SameAsCors,
+///     report_only: None,
+///     violation_reporter: None,
+///     observers: vec![],
+///     debug: None
 ///   };
 ///
 ///   let chain = cors.decorate(handler);
@@ -176,6 +459,9 @@ pub struct CorsMiddleware {
     pub exposed_headers: Vec<UniCase<String>>,
     /// Whether to allow clients to send cookies to this resource or not
     pub allow_credentials: bool,
+    /// Restricts which origins are granted credentials. Only relevant
+    /// if `allow_credentials` is set
+    pub credentials_policy: CredentialsPolicy,
     /// Defines the max cache lifetime for operations allowed on this
     /// resource
     pub max_age_seconds: u32,
@@ -184,12 +470,41 @@ pub struct CorsMiddleware {
     /// echoing the incoming origin will be preferred.
     /// If credentials are allowed, echoing will always be used.
     pub prefer_wildcard: bool,
+    /// If set, WebSocket handshakes are checked against the allowed origins
+    /// as well, and rejected with `403 Forbidden` if the origin isn't allowed.
+    /// Browsers don't apply CORS to WebSockets, but do send the Origin header,
+    /// so this protects against cross-site WebSocket hijacking. No CORS headers
+    /// are added to handshake responses.
+    pub guard_websockets: bool,
+    /// The `Cross-Origin-Resource-Policy` to set on responses, unless already
+    /// set by the handler.
+    pub resource_policy: ResourcePolicy,
+    /// Whether to set `Timing-Allow-Origin` on responses to normal requests
+    pub timing_policy: TimingPolicy,
+    /// A candidate policy which is evaluated for every CORS request, but not
+    /// applied. Requests it would reject are reported.
+    pub report_only: Option<CandidatePolicy>,
+    /// Receives a report for every CORS request rejected by this middleware,
+    /// and for every request the report-only policy would reject
+    pub violation_reporter: Option<Arc<dyn ViolationReporter>>,
+    /// Observers notified of every decision made by this middleware
+    pub observers: Vec<Arc<dyn CorsObserver>>,
+    /// If set, decisions are explained to trusted origins in a response header
+    pub debug: Option<CorsDebug>,
 }
 
 /// Returns all standard HTTP verbs:
-/// `[Options, Get, Post, Put, Delete, Head, Trace, Connect, Patch]`
+/// `[OPTIONS, GET, POST, PUT, DELETE, HEAD, TRACE, CONNECT, PATCH]`
 pub fn all_std_methods() -> Vec<Method> {
-    vec![Options, Get, Post, Put, Delete, Head, Trace, Connect, Patch]
+    vec![Method::OPTIONS,
+         Method::GET,
+         Method::POST,
+         Method::PUT,
+         Method::DELETE,
+         Method::HEAD,
+         Method::TRACE,
+         Method::CONNECT,
+         Method::PATCH]
 }
 
 /// Returns HTTP Headers commonly set by clients (js frontend frameworks and the like):
@@ -203,6 +518,13 @@ pub fn common_req_headers() -> Vec<unicase::UniCase<String>> {
 impl CorsMiddleware {
     /// New middleware with sensible permissive settings.
     /// Allows any origin.
+    /// Does not guard WebSocket handshakes.
+    /// Does not set Cross-Origin-Resource-Policy.
+    /// Does not set Timing-Allow-Origin.
+    /// Has no report-only policy.
+    /// Does not report violations.
+    /// Has no observers.
+    /// Does not explain decisions.
     /// Allows all standard HTTP methods.
     /// Allows common request headers (as defined by `common_req_headers()`.
     /// Does not expose any headers.
@@ -210,273 +532,79 @@ impl CorsMiddleware {
     /// Sets MaxAge to 60 minutes.
     pub fn permissive() -> CorsMiddleware {
         CorsMiddleware {
-            allowed_origins: AllowedOrigins::Any { allow_null: false },
+            allowed_origins: AllowedOrigins::Any {
+                allow_null: false,
+                allow_opaque: false,
+            },
             allowed_methods: all_std_methods(),
             allowed_headers: common_req_headers(),
             exposed_headers: vec![],
             allow_credentials: false,
+            credentials_policy: CredentialsPolicy::AnyOrigin,
             max_age_seconds: 60 * 60,
             prefer_wildcard: false,
+            guard_websockets: false,
+            resource_policy: ResourcePolicy::Omit,
+            timing_policy: TimingPolicy::Omit,
+            report_only: None,
+            violation_reporter: None,
+            observers: vec![],
+            debug: None,
         }
     }
 
-    /// These are all headers which can influence the outcome of
-    /// any given CORS request.
-    fn vary_headers() -> Vec<UniCase<String>> {
-        vec![UniCase("Origin".to_owned()),
-             UniCase("Access-Control-Request-Method".to_owned()),
-             UniCase("Access-Control-Request-Headers".to_owned())]
-    }
-
-    /// Handle a potential CORS request. Detects if this is a
-    /// preflight or normal method, adding CORS headers as appropriate
-    fn handle(&self, req: &mut Request, handler: &Handler) -> IronResult<Response> {
-        // http://stackoverflow.com/questions/14015118/
-        // what-is-the-expected-response-to-an-invalid-cors-request
-        // http://stackoverflow.com/questions/32331737/
-        // how-can-i-identify-a-cors-preflight-request
-        let res = if req.method == Options &&
-                     req.headers.get::<AccessControlRequestMethod>().is_some() {
-            self.handle_preflight(req, handler)
-        } else {
-            self.handle_normal(req, handler)
-        };
-        // Vary-Headers are outside the CORS specification, but still important for
-        // caching. These should be set unconditionally for all resources covered by CORS
-        match res {
-            Ok(mut r) => {
-                r.headers.set(Vary::Items(CorsMiddleware::vary_headers()));
-                Ok(r)
-            }
-            x => x,
-        }
-    }
-
-    /// Handle a preflight request
-    fn handle_preflight(&self, req: &mut Request, _: &Handler) -> IronResult<Response> {
-        // Successful preflight status code is NoContent
-        let mut res = Response::with(status::NoContent);
-
-        // - Preflight request
-        // - 1.If the Origin header is not present terminate this set of steps. The request is
-        // - outside the scope of this specification.
-        let maybe_origin = req.headers.get::<OriginHeader>();
-        if maybe_origin.is_none() {
-            let resp = Response::with((status::BadRequest,
-                                       "Preflight request without Origin header"));
-            return Ok(resp);
-        }
-        let origin = maybe_origin.unwrap();
-        //
-        // - 2.If the value of the Origin header is not a case-sensitive match for any of the
-        // - values in list of origins do not set any additional headers and terminate this
-        // - set of steps.
-        //
-        // - Note: Always matching is acceptable since the list of origins can be unbounded.
-        //
-        // - Note: The Origin header can only contain a single origin as the user agent
-        //       will not follow redirects.
-        //
-        let origin_str = origin.to_string();
-        let allowed_origin =
-            self.allowed_origins.allowed_for(&origin_str,
-                                             self.allow_credentials,
-                                             self.prefer_wildcard);
-        if allowed_origin.is_none() {
-            let resp = Response::with((status::BadRequest,
-                                       format!("Preflight request requesting \
-                                       disallowed origin '{}'",
-                                               origin_str)));
-            return Ok(resp);
-        }
-        //
-        // - 3. Let method be the value as result of parsing the Access-Control-Request-Method
-        // - header.
-        //
-        // - If there is no Access-Control-Request-Method header or if parsing failed, do not
-        // - set any additional headers and terminate this set of steps. The request is
-        // - outside the scope of this specification.
-
-        // We can assume that this header exists, since we already checked that before
-        // classifying the request as preflight
-        let requested_method = req.headers.get::<AccessControlRequestMethod>().unwrap();
-
-        //
-        // - 4. Let header field-names be the values as result of parsing the
-        // - Access-Control-Request-Headers headers.
-        //
-        // - If there are no Access-Control-Request-Headers headers let header field-names be
-        // - the empty list.
-        //
-        // - If parsing failed do not set any additional headers and terminate this set of
-        // - steps. The request is outside the scope of this specification.
-        //
-        let empty_vec: Vec<UniCase<String>> = vec![];
-        let maybe_requested_headers = req.headers.get::<AccessControlRequestHeaders>();
-        let requested_headers: &Vec<UniCase<String>> = if maybe_requested_headers.is_some() {
-            &maybe_requested_headers.unwrap().0
-        } else {
-            &empty_vec
-        };
-        // - 5.If method is not a case-sensitive match for any of the values in list of
-        // -   methods do not set any additional headers and terminate this set of steps.
-        //
-        // - Always matching is acceptable since the list of methods can be unbounded.
-        //
-        if !self.allowed_methods.contains(requested_method) {
-            return Ok(Response::with((status::BadRequest,
-                                      format!("Preflight request requesting disallowed method {}",
-                                              requested_method))));
-        }
-        // - 6. If any of the header field-names is not a ASCII case-insensitive match for any
-        // - of the values in list of headers do not set any additional headers and terminate
-        // - this set of steps.
-        let requested_headers_set: HashSet<UniCase<String>> =
-            HashSet::from_iter(requested_headers.iter().cloned());
-        let allowed_headers_set: HashSet<UniCase<String>> =
-            HashSet::from_iter(self.allowed_headers.iter().cloned());
-        let disallowed_headers: HashSet<UniCase<String>> =
-            requested_headers_set.difference(&allowed_headers_set).cloned().collect();
-        if !disallowed_headers.is_empty() {
-            let a = disallowed_headers.iter()
-                .map(|uh| uh.to_string())
-                .collect::<Vec<_>>()
-                .join(",");
-            let msg = format!("Preflight request requesting disallowed header(s) {}", a);
-            return Ok(Response::with((status::BadRequest, msg)));
-
-        }
-        //
-        // - Always matching is acceptable since the list of headers can be unbounded.
-        //
-        // - 7. If the resource supports credentials add a single Access-Control-Allow-Origin
-        // - header, with the value of the Origin header as value, and add a single
-        // - Access-Control-Allow-Credentials header with the case-sensitive string "true" as
-        // - value.
-        //
-        // - Otherwise, add a single Access-Control-Allow-Origin header, with either the
-        // - value of the Origin header or the string "*" as value.
-        //
-        // - The string "*" cannot be used for a resource that supports credentials.
-        //
-        if self.allow_credentials {
-            res.headers.set(AccessControlAllowCredentials);
-        }
-        res.headers.set(AccessControlAllowOrigin::Value(allowed_origin.unwrap()));
-        // - 8. Optionally add a single Access-Control-Max-Age header with as value the amount
-        // - of seconds the user agent is allowed to cache the result of the request.
-        res.headers.set(AccessControlMaxAge(self.max_age_seconds));
-        //
-        // - 9. If method is a simple method this step may be skipped.
-        //
-        // - Add one or more Access-Control-Allow-Methods headers consisting of (a subset of)
-        // - the list of methods.
-        //
-        // - If a method is a simple method it does not need to be listed, but this is not
-        // - prohibited.
-        //
-        // - Since the list of methods can be unbounded, simply returning the method
-        // - indicated by Access-Control-Request-Method (if supported) can be enough.
-        //
-        res.headers.set(AccessControlAllowMethods(self.allowed_methods.clone()));
-        // - 10.If each of the header field-names is a simple header and none is Content-Type,
-        // - this step may be skipped.
-        //
-        // - Add one or more Access-Control-Allow-Headers headers consisting of (a subset of)
-        // - the list of headers.
-        //
-        // - If a header field name is a simple header and is not Content-Type, it is not
-        // - required to be listed. Content-Type is to be listed as only a subset of its
-        // - values makes it qualify as simple header.
-        //
-        // - Since the list of headers can be unbounded, simply returning supported headers
-        // - from Access-Control-Allow-Headers can be enough.
-        res.headers.set(AccessControlAllowHeaders(self.allowed_headers.clone()));
-        Ok(res)
-    }
-
-    /// Handle a normal (i.e non-preflight) CORS request
-    fn handle_normal(&self, req: &mut Request, handler: &Handler) -> IronResult<Response> {
-        // Normal request
-        // - 1.If the Origin header is not present terminate this set of steps. The request is
-        // - outside the scope of this specification.
-        let have_origin;
-        {
-            let maybe_origin = req.headers.get::<OriginHeader>();
-            have_origin = maybe_origin.is_some();
-        }
-        if !have_origin {
-            // No origin, treat as normal request.
-            // We could return error here if we wanted according to
-            // https://tools.ietf.org/id/draft-abarth-origin-03.html#rfc.section.6
-            return handler.handle(req);
-        }
-        //
-        // - 2.If the value of the Origin header is not a case-sensitive match for any of the
-        // - values in list of origins, do not set any additional headers and terminate this
-        // - set of steps.
-        //
-        // - Note: Always matching is acceptable since the list of origins can be unbounded.
-        //
-        let origin = req.headers
-            .get::<OriginHeader>()
-            .unwrap()
-            .clone();
-        let origin_str = origin.to_string();
-        let allowed_origin =
-            self.allowed_origins.allowed_for(&origin_str,
-                                             self.allow_credentials,
-                                             self.prefer_wildcard);
-        if allowed_origin.is_none() {
-            let resp = Response::with((status::BadRequest,
-                                       format!("Normal request requesting \
-                                       disallowed origin '{}'",
-                                               origin_str)));
-            return Ok(resp);
+    /// Decides whether credentials are granted to the given origin. Returns
+    /// `None` if the origin is to be rejected since it's insecure.
+    fn credentials_for(&self, origin_str: &str) -> Option<bool> {
+        if !self.allow_credentials {
+            return Some(false);
         }
-        let result = handler.handle(req);
-        match result {
-            Ok(mut res) => {
-                //
-                // - 3. If the resource supports credentials add a single
-                // - Access-Control-Allow-Origin
-                // - header, with the value of the Origin header as value, and add a single
-                // - Access-Control-Allow-Credentials header with the case-sensitive string
-                // - "true" as value.
-                //
-                // - Otherwise, add a single Access-Control-Allow-Origin header, with either the
-                // - value of the Origin header or the string "*" as value.
-                //
-                // - Note: The string "*" cannot be used for a resource that supports credentials.
-                if self.allow_credentials {
-                    res.headers.set(AccessControlAllowCredentials);
-                }
-                res.headers.set(AccessControlAllowOrigin::Value(allowed_origin.unwrap()));
-                //
-                // - 4. If the list of exposed headers is not empty add one or more
-                // - Access-Control-Expose-Headers headers, with as values the header field names
-                // - given in the list of exposed headers.
-                if !self.exposed_headers.is_empty() {
-                    res.headers.set(AccessControlExposeHeaders(self.exposed_headers.clone()));
+        match self.credentials_policy {
+            CredentialsPolicy::AnyOrigin => Some(true),
+            CredentialsPolicy::SecureOrigins { ref loopback, reject_insecure } => {
+                let secure = match Origin::parse_allow_opaque(origin_str) {
+                    Ok(origin) => {
+                        origin.is_secure() || (origin.is_loopback() && loopback.contains(&origin))
+                    }
+                    Err(_) => false,
+                };
+                if secure {
+                    Some(true)
+                } else if reject_insecure {
+                    None
+                } else {
+                    Some(false)
                 }
-                Ok(res)
             }
-            _ => result,
         }
-
     }
 
-    /// Util function for wrapping the supplied handler with this CorsMiddleware.
-    /// Works by constructing a chain with only this middleware linked.
-    pub fn decorate<T: Handler>(self, handler: T) -> Chain {
-        let mut chain = Chain::new(handler);
-        chain.link_around(self);
-        chain
+    /// Returns the Cross-Origin-Resource-Policy to set, if any
+    fn cross_origin_resource_policy(&self) -> Option<CrossOriginResourcePolicy> {
+        match self.resource_policy {
+            ResourcePolicy::Omit => None,
+            ResourcePolicy::FromAllowedOrigins => Some(self.allowed_origins.resource_policy()),
+            ResourcePolicy::Fixed(policy) => Some(policy),
+        }
     }
-}
 
-impl AroundMiddleware for CorsMiddleware {
-    fn around(self, handler: Box<Handler>) -> Box<Handler> {
-        Box::new(move |req: &mut Request| self.handle(req, &handler))
+    /// Returns the Timing-Allow-Origin to set for an allowed origin, given the
+    /// value of Access-Control-Allow-Origin
+    fn timing_allow_origin(&self,
+                           origin_str: &str,
+                           allowed_origin: &str)
+                           -> Option<TimingAllowOrigin> {
+        let value = match self.timing_policy {
+            TimingPolicy::Omit => return None,
+            TimingPolicy::SameAsCors => allowed_origin.to_owned(),
+            TimingPolicy::Independent(ref allowed) => {
+                allowed.allowed_for(origin_str, false, self.prefer_wildcard)?
+            }
+        };
+        Some(if value == "*" {
+            TimingAllowOrigin::Any
+        } else {
+            TimingAllowOrigin::Value(vec![value])
+        })
     }
 }
@@ -0,0 +1,360 @@
+//! Metrics on CORS decisions in the Prometheus text format, see `CorsMetrics`.
+
+#[cfg(feature = "iron")]
+use iron::prelude::*;
+#[cfg(feature = "iron")]
+use iron::status;
+#[cfg(feature = "iron")]
+use iron::headers::ContentType;
+#[cfg(feature = "iron")]
+use iron::middleware::Handler;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+#[cfg(feature = "iron")]
+use std::sync::Arc;
+use std::sync::Mutex;
+use evaluate::CorsRequest;
+use observer::{CorsObserver, CorsDecision};
+use reporting::{Violation, ViolationKind};
+
+/// Upper bounds of the buckets of the requested headers histogram
+const HEADER_BUCKETS: [usize; 6] = [0, 1, 2, 4, 8, 16];
+
+/// The label used for origins which don't get a label of their own
+const OTHER: &str = "other";
+
+/// The number of allowed requests after which the labelled origins are
+/// chosen anew
+const RELABEL_INTERVAL: u64 = 100;
+
+/// How many origins are counted for each origin getting a label
+const CANDIDATES_PER_LABEL: usize = 4;
+
+/// A histogram of requested header counts for one origin
+#[derive(Default, Clone)]
+struct Histogram {
+    buckets: [u64; 6],
+    sum: u64,
+    count: u64,
+}
+
+impl Histogram {
+    fn merge(&mut self, other: &Histogram) {
+        for (bucket, count) in self.buckets.iter_mut().zip(other.buckets.iter()) {
+            *bucket += count;
+        }
+        self.sum += other.sum;
+        self.count += other.count;
+    }
+}
+
+#[derive(Default)]
+struct State {
+    labelled_origins: HashSet<String>,
+    // Approximate counts of recently allowed requests by origin, kept with
+    // the space-saving algorithm
+    candidates: HashMap<String, u64>,
+    // Allowed requests since the labelled origins were last chosen
+    since_relabel: u64,
+    preflights: BTreeMap<String, u64>,
+    normal: BTreeMap<String, u64>,
+    passthrough: u64,
+    rejected: BTreeMap<(String, &'static str), u64>,
+    requested_headers: BTreeMap<String, Histogram>,
+}
+
+/// Counts the decisions of `CorsMiddleware`. Register it as an observer, see
+/// `CorsMiddleware::observers`, and expose it with `render()`, such as with a
+/// `MetricsHandler` under Iron.
+///
+/// The following metrics are kept:
+///
+/// * `corsware_preflight_requests_total{origin}`: allowed preflight requests
+/// * `corsware_normal_requests_total{origin}`: allowed normal CORS requests
+/// * `corsware_passthrough_requests_total`: requests without Origin header
+/// * `corsware_rejected_requests_total{origin,reason}`: rejected CORS requests
+/// * `corsware_requested_headers{origin}`: histogram of the number of headers
+///   requested by preflight requests
+///
+/// To bound the number of time series, only the top `max_origins` origins by
+/// recently allowed requests get a label of their own. All other origins,
+/// including all origins which have only ever been rejected, are labelled
+/// `other`. The top origins are chosen anew every 100 allowed requests, and
+/// the counts of an origin losing its label are added to `other`, so that
+/// its series disappear while all counters keep increasing.
+///
+/// #Example
+#[cfg_attr(feature = "iron", doc = "```")]
+#[cfg_attr(not(feature = "iron"), doc = "```ignore")]
+/// extern crate iron;
+/// extern crate mount;
+/// extern crate corsware;
+/// use corsware::{CorsMiddleware, CorsMetrics, MetricsHandler};
+/// use iron::prelude::*;
+/// use iron::status;
+/// use mount::Mount;
+/// use std::sync::Arc;
+///
+/// fn main() {
+///   let handler = |_: &mut Request| {
+///       Ok(Response::with((status::Ok, "Hello world!")))
+///   };
+///   let metrics = Arc::new(CorsMetrics::new(20));
+///   let cors = CorsMiddleware {
+///     observers: vec![metrics.clone()],
+///     ..CorsMiddleware::permissive()
+///   };
+///   let mut mount = Mount::new();
+///   mount.mount("/api", cors.decorate(handler));
+///   mount.mount("/metrics", MetricsHandler::new(metrics));
+///   let mut listening = Iron::new(mount).http("localhost:0").unwrap();
+///   listening.close().unwrap();
+/// }
+/// ```
+pub struct CorsMetrics {
+    max_origins: usize,
+    state: Mutex<State>,
+}
+
+impl CorsMetrics {
+    /// New registry giving at most `max_origins` origins, the top ones by
+    /// recently allowed requests, a label of their own
+    pub fn new(max_origins: usize) -> CorsMetrics {
+        CorsMetrics {
+            max_origins,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Returns the label for the given origin. Allowed origins are counted,
+    /// and given a label of their own as long as there's room.
+    fn label(&self, state: &mut State, origin: &str, allowed: bool) -> String {
+        if allowed {
+            self.count(state, origin);
+        }
+        if state.labelled_origins.contains(origin) {
+            return origin.to_owned();
+        }
+        if allowed && state.labelled_origins.len() < self.max_origins {
+            state.labelled_origins.insert(origin.to_owned());
+            return origin.to_owned();
+        }
+        OTHER.to_owned()
+    }
+
+    /// Counts an allowed request, choosing the labelled origins anew every
+    /// `RELABEL_INTERVAL` requests
+    fn count(&self, state: &mut State, origin: &str) {
+        let capacity = self.max_origins * CANDIDATES_PER_LABEL;
+        if capacity == 0 {
+            return;
+        }
+        if let Some(count) = state.candidates.get_mut(origin) {
+            *count += 1;
+        } else if state.candidates.len() < capacity {
+            state.candidates.insert(origin.to_owned(), 1);
+        } else {
+            // Space-saving: the new origin replaces the least counted one,
+            // inheriting its count as an upper bound of its own
+            let (least, min) = state.candidates
+                .iter()
+                .min_by_key(|&(o, &c)| (c, o.clone()))
+                .map(|(o, &c)| (o.clone(), c))
+                .unwrap();
+            state.candidates.remove(&least);
+            state.candidates.insert(origin.to_owned(), min + 1);
+        }
+        state.since_relabel += 1;
+        if state.since_relabel >= RELABEL_INTERVAL {
+            self.relabel(state);
+        }
+    }
+
+    /// Gives the top `max_origins` origins a label of their own, folding the
+    /// series of the origins losing their label into `other`
+    fn relabel(&self, state: &mut State) {
+        let mut candidates: Vec<(&String, &u64)> = state.candidates.iter().collect();
+        candidates.sort_by(|&(a, ca), &(b, cb)| cb.cmp(ca).then_with(|| a.cmp(b)));
+        let top: HashSet<String> =
+            candidates.into_iter().take(self.max_origins).map(|(o, _)| o.clone()).collect();
+        let dropped: Vec<String> = state.labelled_origins.difference(&top).cloned().collect();
+        for origin in &dropped {
+            CorsMetrics::fold_into_other(state, origin);
+        }
+        state.labelled_origins = top;
+        // Halving the counts makes the choice follow recent traffic
+        for count in state.candidates.values_mut() {
+            *count /= 2;
+        }
+        state.candidates.retain(|_, &mut count| count > 0);
+        state.since_relabel = 0;
+    }
+
+    /// Adds the counts of the given origin to `other`, removing its series
+    fn fold_into_other(state: &mut State, origin: &str) {
+        for counters in &mut [&mut state.preflights, &mut state.normal] {
+            if let Some(count) = counters.remove(origin) {
+                *counters.entry(OTHER.to_owned()).or_insert(0) += count;
+            }
+        }
+        let rejected: Vec<(String, &'static str)> =
+            state.rejected.keys().filter(|key| key.0 == origin).cloned().collect();
+        for key in rejected {
+            let count = state.rejected.remove(&key).unwrap();
+            *state.rejected.entry((OTHER.to_owned(), key.1)).or_insert(0) += count;
+        }
+        if let Some(histogram) = state.requested_headers.remove(origin) {
+            state.requested_headers.entry(OTHER.to_owned()).or_default().merge(&histogram);
+        }
+    }
+
+    fn observe_headers(state: &mut State, origin: String, count: usize) {
+        let histogram = state.requested_headers.entry(origin).or_default();
+        for (bucket, &bound) in histogram.buckets.iter_mut().zip(HEADER_BUCKETS.iter()) {
+            if count <= bound {
+                *bucket += 1;
+            }
+        }
+        histogram.sum += count as u64;
+        histogram.count += 1;
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let state = self.state.lock().unwrap();
+        let mut out = String::new();
+        write_counter(&mut out,
+                      "corsware_preflight_requests_total",
+                      "Allowed CORS preflight requests.",
+                      state.preflights.iter().map(|(o, &v)| (format!("origin={}", quote(o)), v)));
+        write_counter(&mut out,
+                      "corsware_normal_requests_total",
+                      "Allowed normal CORS requests.",
+                      state.normal.iter().map(|(o, &v)| (format!("origin={}", quote(o)), v)));
+        out.push_str("# HELP corsware_passthrough_requests_total Requests without Origin \
+                      header.\n\
+                      # TYPE corsware_passthrough_requests_total counter\n");
+        writeln!(out, "corsware_passthrough_requests_total {}", state.passthrough).unwrap();
+        write_counter(&mut out,
+                      "corsware_rejected_requests_total",
+                      "Rejected CORS requests.",
+                      state.rejected.iter().map(|(&(ref o, reason), &v)| {
+                          (format!("origin={},reason={}", quote(o), quote(reason)), v)
+                      }));
+        out.push_str("# HELP corsware_requested_headers Number of headers requested by \
+                      preflight requests.\n\
+                      # TYPE corsware_requested_headers histogram\n");
+        for (origin, histogram) in &state.requested_headers {
+            let origin = quote(origin);
+            for (count, bound) in histogram.buckets.iter().zip(HEADER_BUCKETS.iter()) {
+                writeln!(out,
+                         "corsware_requested_headers_bucket{{origin={},le=\"{}\"}} {}",
+                         origin,
+                         bound,
+                         count)
+                    .unwrap();
+            }
+            writeln!(out,
+                     "corsware_requested_headers_bucket{{origin={},le=\"+Inf\"}} {}",
+                     origin,
+                     histogram.count)
+                .unwrap();
+            writeln!(out,
+                     "corsware_requested_headers_sum{{origin={}}} {}",
+                     origin,
+                     histogram.sum)
+                .unwrap();
+            writeln!(out,
+                     "corsware_requested_headers_count{{origin={}}} {}",
+                     origin,
+                     histogram.count)
+                .unwrap();
+        }
+        out
+    }
+}
+
+/// Returns the value of the `reason` label for a rejection
+fn reason_label(kind: ViolationKind) -> &'static str {
+    match kind {
+        ViolationKind::MissingOrigin => "missing_origin",
+        ViolationKind::MultipleOrigins => "multiple_origins",
+        ViolationKind::MalformedOrigin => "malformed",
+        ViolationKind::Origin => "origin",
+        ViolationKind::InsecureOrigin => "insecure_origin",
+        ViolationKind::Method => "method",
+        ViolationKind::Headers => "header",
+    }
+}
+
+/// Quotes and escapes a label value
+fn quote(value: &str) -> String {
+    format!("\"{}\"",
+            value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
+
+fn write_counter<I>(out: &mut String, name: &str, help: &str, samples: I)
+    where I: Iterator<Item = (String, u64)>
+{
+    writeln!(out, "# HELP {} {}", name, help).unwrap();
+    writeln!(out, "# TYPE {} counter", name).unwrap();
+    for (labels, value) in samples {
+        writeln!(out, "{}{{{}}} {}", name, labels, value).unwrap();
+    }
+}
+
+impl CorsObserver for CorsMetrics {
+    fn on_preflight(&self, _: &CorsRequest, _: &str, decision: &CorsDecision) {
+        let mut state = self.state.lock().unwrap();
+        let origin = self.label(&mut state, &decision.origin, true);
+        *state.preflights.entry(origin.clone()).or_insert(0) += 1;
+        CorsMetrics::observe_headers(&mut state, origin, decision.headers.len());
+    }
+
+    fn on_normal(&self, _: &CorsRequest, _: &str, decision: &CorsDecision) {
+        let mut state = self.state.lock().unwrap();
+        let origin = self.label(&mut state, &decision.origin, true);
+        *state.normal.entry(origin).or_insert(0) += 1;
+    }
+
+    fn on_rejected(&self, _: &CorsRequest, _: &str, rejection: &Violation) {
+        let mut state = self.state.lock().unwrap();
+        let origin = if rejection.origin.is_empty() {
+            OTHER.to_owned()
+        } else {
+            self.label(&mut state, &rejection.origin, false)
+        };
+        *state.rejected.entry((origin.clone(), reason_label(rejection.kind))).or_insert(0) += 1;
+        if rejection.preflight {
+            CorsMetrics::observe_headers(&mut state, origin, rejection.headers.len());
+        }
+    }
+
+    fn on_passthrough_no_origin(&self, _: &CorsRequest, _: &str) {
+        self.state.lock().unwrap().passthrough += 1;
+    }
+}
+
+/// An Iron handler rendering `CorsMetrics` in the Prometheus text format,
+/// meant to be mounted at `/metrics`
+#[cfg(feature = "iron")]
+pub struct MetricsHandler {
+    metrics: Arc<CorsMetrics>,
+}
+
+#[cfg(feature = "iron")]
+impl MetricsHandler {
+    /// New handler rendering the given metrics
+    pub fn new(metrics: Arc<CorsMetrics>) -> MetricsHandler {
+        MetricsHandler { metrics }
+    }
+}
+
+#[cfg(feature = "iron")]
+impl Handler for MetricsHandler {
+    fn handle(&self, _: &mut Request) -> IronResult<Response> {
+        let mut res = Response::with((status::Ok, self.metrics.render()));
+        res.headers.set(ContentType("text/plain; version=0.0.4".parse().unwrap()));
+        Ok(res)
+    }
+}
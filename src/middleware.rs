@@ -0,0 +1,95 @@
+//! The Iron adapter of `CorsMiddleware`, enabled by the `iron` feature.
+
+use iron::prelude::*;
+use iron::status;
+use iron::headers::ContentType;
+use iron::middleware::{AroundMiddleware, Handler};
+use mount::OriginalUrl;
+use handler::{cors_headers, cors_request, set_headers};
+use headers::CrossOriginResourcePolicy;
+use evaluate::{CorsRequest, CorsOutcome, is_websocket_handshake, sets_response_header};
+use CorsMiddleware;
+
+impl CorsMiddleware {
+    /// Handle a potential CORS request. Detects if this is a
+    /// preflight or normal method, adding CORS headers as appropriate
+    fn handle(&self, req: &mut Request, handler: &dyn Handler) -> IronResult<Response> {
+        // http://stackoverflow.com/questions/14015118/
+        // what-is-the-expected-response-to-an-invalid-cors-request
+        // http://stackoverflow.com/questions/32331737/
+        // how-can-i-identify-a-cors-preflight-request
+        let headers = cors_headers(&req.headers);
+        let cors_req = match cors_request(&req.method, &headers) {
+            Some(cors_req) => cors_req,
+            None => return handler.handle(req),
+        };
+        if self.guard_websockets && is_websocket_handshake(&headers) {
+            return self.handle_websocket(req, handler, &cors_req);
+        }
+        let outcome = self.process(&cors_req, &CorsMiddleware::path(req));
+        let mut res = match outcome {
+            CorsOutcome::NotCors => handler.handle(req)?,
+            CorsOutcome::Preflight(headers) => {
+                // Successful preflight status code is NoContent
+                let mut res = Response::with(status::NoContent);
+                set_headers(&mut res.headers, &headers);
+                res
+            }
+            CorsOutcome::Normal(headers) => {
+                let mut res = handler.handle(req)?;
+                set_headers(&mut res.headers, &headers);
+                res
+            }
+            CorsOutcome::Rejected(rejection) => {
+                let (body, content_type) = self.rejection_body(&cors_req, &rejection);
+                let mut res = Response::with((status::BadRequest, body));
+                if content_type == "application/json" {
+                    res.headers.set(ContentType::json());
+                }
+                res
+            }
+        };
+        let has_resource_policy = res.headers.has::<CrossOriginResourcePolicy>();
+        for (name, value) in self.response_headers(&cors_req) {
+            if sets_response_header(&name, has_resource_policy) {
+                res.headers.set_raw(name, vec![value.into_bytes()]);
+            }
+        }
+        Ok(res)
+    }
+
+    /// Returns the path of the request, including the prefix of any
+    /// enclosing `Mount`
+    fn path(req: &Request) -> String {
+        let url = req.extensions.get::<OriginalUrl>().unwrap_or(&req.url);
+        format!("/{}", url.path().join("/"))
+    }
+
+    /// Handle a WebSocket handshake. The origin is checked the same way as for
+    /// CORS requests, but failures result in `403 Forbidden` and no CORS headers
+    /// are set.
+    fn handle_websocket(&self,
+                        req: &mut Request,
+                        handler: &dyn Handler,
+                        cors_req: &CorsRequest)
+                        -> IronResult<Response> {
+        match self.check_websocket(cors_req) {
+            Ok(()) => handler.handle(req),
+            Err(message) => Ok(Response::with((status::Forbidden, message))),
+        }
+    }
+
+    /// Util function for wrapping the supplied handler with this CorsMiddleware.
+    /// Works by constructing a chain with only this middleware linked.
+    pub fn decorate<T: Handler>(self, handler: T) -> Chain {
+        let mut chain = Chain::new(handler);
+        chain.link_around(self);
+        chain
+    }
+}
+
+impl AroundMiddleware for CorsMiddleware {
+    fn around(self, handler: Box<dyn Handler>) -> Box<dyn Handler> {
+        Box::new(move |req: &mut Request| self.handle(req, &handler))
+    }
+}
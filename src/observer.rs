@@ -0,0 +1,61 @@
+//! Hooks for observing the decisions of `CorsMiddleware`, see `CorsObserver`.
+
+use http::Method;
+use unicase::UniCase;
+use evaluate::CorsRequest;
+use reporting::Violation;
+
+/// A CORS request which the middleware lets through
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CorsDecision {
+    /// The Origin header of the request
+    pub origin: String,
+    /// The requested method, i.e. Access-Control-Request-Method for
+    /// preflight requests and the method of the request otherwise
+    pub method: Method,
+    /// The requested headers, i.e. Access-Control-Request-Headers for
+    /// preflight requests. Empty for normal requests.
+    pub headers: Vec<UniCase<String>>,
+    /// The value of Access-Control-Allow-Origin, i.e. the origin or `*`
+    pub allow_origin: String,
+    /// Whether credentials are allowed
+    pub allow_credentials: bool,
+}
+
+/// Observes every decision made by `CorsMiddleware`, such as for audit logs,
+/// metrics or sampling. Register observers with `CorsMiddleware::observers`.
+///
+/// All callbacks do nothing by default, so implementations only need to
+/// override the ones they're interested in. Callbacks run on the request
+/// thread, before the response is sent, so they should be quick. They're
+/// given the parts of the request CORS decisions are based on and its path,
+/// so observers work the same with every adapter, see
+/// `CorsMiddleware::process()`.
+///
+/// #Example
+/// ```
+/// use corsware::{CorsObserver, CorsDecision, CorsRequest};
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// struct PreflightCounter(AtomicUsize);
+///
+/// impl CorsObserver for PreflightCounter {
+///     fn on_preflight(&self, _: &CorsRequest, _: &str, _: &CorsDecision) {
+///         self.0.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+/// ```
+pub trait CorsObserver: Send + Sync {
+    /// Called when a preflight request is allowed
+    fn on_preflight(&self, _req: &CorsRequest, _path: &str, _decision: &CorsDecision) {}
+
+    /// Called when a normal CORS request is allowed, before it's handled
+    fn on_normal(&self, _req: &CorsRequest, _path: &str, _decision: &CorsDecision) {}
+
+    /// Called when a CORS request is rejected
+    fn on_rejected(&self, _req: &CorsRequest, _path: &str, _rejection: &Violation) {}
+
+    /// Called when a request without Origin header is passed through to the
+    /// handler, since it's not a CORS request
+    fn on_passthrough_no_origin(&self, _req: &CorsRequest, _path: &str) {}
+}
@@ -0,0 +1,73 @@
+use std::net::IpAddr;
+
+/// A block of IP addresses in CIDR notation, such as `10.0.0.0/8` or `fd00::/8`.
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub struct CidrBlock {
+    address: IpAddr,
+    prefix_len: u8,
+}
+
+impl CidrBlock {
+    /// Parses a CIDR block. An address without prefix length is taken
+    /// to be a block containing only that address.
+    /// #Errors
+    /// Errors are returned if the address or the prefix length cannot be
+    /// parsed, or if the prefix length is too large for the address family.
+    ///
+    /// #Examples
+    /// ```
+    /// use corsware::CidrBlock;
+    /// let block = CidrBlock::parse("10.20.0.0/16").unwrap();
+    /// assert!(block.contains(&"10.20.30.40".parse().unwrap()));
+    /// assert!(!block.contains(&"10.21.30.40".parse().unwrap()));
+    /// ```
+    pub fn parse(s: &str) -> Result<CidrBlock, String> {
+        let mut parts = s.splitn(2, '/');
+        let address = parts.next()
+            .unwrap_or("")
+            .parse::<IpAddr>()
+            .map_err(|_| format!("Invalid IP address in CIDR block '{}'", s))?;
+        let max_len = match address {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        let prefix_len = match parts.next() {
+            None => max_len,
+            Some(len) => {
+                match len.parse::<u8>() {
+                    Ok(len) if len <= max_len => len,
+                    _ => return Err(format!("Invalid prefix length in CIDR block '{}'", s)),
+                }
+            }
+        };
+        Ok(CidrBlock {
+               address,
+               prefix_len,
+           })
+    }
+
+    /// Returns true if the given address is within this block. Addresses of
+    /// the other address family are never contained.
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.address, *ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = mask(u128::from(u32::MAX), 32, self.prefix_len);
+                u128::from(u32::from(net)) & mask == u128::from(u32::from(ip)) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = mask(u128::MAX, 128, self.prefix_len);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Returns a mask with the `prefix_len` most significant of `bits` bits set
+fn mask(all: u128, bits: u8, prefix_len: u8) -> u128 {
+    if prefix_len == 0 {
+        0
+    } else {
+        all & (all << (bits - prefix_len))
+    }
+}
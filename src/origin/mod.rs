@@ -1,36 +1,106 @@
 extern crate url;
-extern crate iron;
+extern crate idna;
 
 use self::url::Url;
-use std::ascii::AsciiExt;
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+pub use self::cidr::CidrBlock;
+pub use self::syntax::SyntaxError;
+
+mod cidr;
+mod psl;
+mod syntax;
+
+/// The host of an origin, classified as either a domain name or an IP address.
+/// Obtained through `Origin::typed_host()`.
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub enum Host {
+    /// A domain name, with all ascii chars lowercased and punycoded
+    Domain(String),
+    /// An IPv4 address
+    Ipv4(Ipv4Addr),
+    /// An IPv6 address. Written within brackets in the origin, as in `http://[::1]`
+    Ipv6(Ipv6Addr),
+}
+
+impl Host {
+    /// Classifies a host as serialized by rust-url. IPv6 addresses are expected to
+    /// be enclosed in brackets.
+    fn parse(host: &str) -> Host {
+        if host.starts_with('[') && host.ends_with(']') {
+            if let Ok(ip) = host[1..host.len() - 1].parse::<Ipv6Addr>() {
+                return Host::Ipv6(ip);
+            }
+        } else if let Ok(ip) = host.parse::<Ipv4Addr>() {
+            return Host::Ipv4(ip);
+        }
+        Host::Domain(host.to_owned())
+    }
+}
 
 /// A struct which implements the concept 'Web Origin' as defined in
 /// https://tools.ietf.org/html/rfc6454.
 ///
-/// This implementation only considers hierarchical URLs and null.
+/// Hierarchical URLs yield `Triple` origins, whatever their scheme. URLs without
+/// hierarchical naming authority, such as `data:` URLs, yield `Opaque` origins,
+/// which correspond to the "fresh globally unique identifier" of the RFC. These are
+/// only produced by `Origin::parse_allow_opaque()`, `Origin::parse()` returns an error
+/// instead.
 ///
-/// The rationale behind skipping random id:s is that any such random origin should
-/// never be equal to another random origin.
-/// This has the implication that it's unneccesary to compare them to
-/// each other and we might as well return parse error and handle that
-/// case separately.
+/// Origins are ordered by scheme, host and port, with `Null` first and `Opaque` last.
 ///
-#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone)]
 pub enum Origin {
     /// The `Null` origin, indicating that a resource lacks a proper origin.
     /// This value is commonly used in the Origin header to indicate that an origin couldn't be
     /// deduced or was deliberitely left out. This value is set instead of omitting the Origin
     //  header, since ommiting the header could just signal a client unaware of the origin concept.
     Null,
-    /// The common origin, formed from a `(schem, host, port)` triple.
+    /// The common origin, formed from a `(scheme, host, port)` triple.
     Triple {
         /// Lower-case scheme
         scheme: String,
         /// Host with all ascii chars lowercased and punycoded
         host: String,
-        /// The explicit port or scheme default port if not explicity set
-        port: u16,
+        /// The explicit port or scheme default port if not explicity set.
+        /// `None` if not set and the scheme has no default port, see
+        /// `Origin::register_default_port()`. The default port is resolved when
+        /// parsing, so registering one doesn't affect origins already parsed.
+        port: Option<u16>,
     },
+    /// A globally unique origin, assigned to resources which lack a proper origin,
+    /// such as `data:` URLs and sandboxed frames. An opaque origin is never equal to
+    /// another opaque origin, not even to one parsed from the same string. Only copies
+    /// of the same value compare equal.
+    Opaque(OpaqueId),
+}
+
+/// The identifier of an opaque origin. A fresh identifier is generated for
+/// each opaque origin, see `Origin::opaque()`.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone)]
+pub struct OpaqueId(usize);
+
+/// The reason a string could not be parsed as a `Triple` origin
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum ParseError {
+    /// The string is not an absolute URL.
+    Malformed(String),
+    /// The string is a valid URL, but its origin is opaque. This is the case
+    /// for URLs without host, such as `data:` URLs, except for `file` URLs.
+    Opaque(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::Malformed(ref msg) |
+            ParseError::Opaque(ref msg) => f.write_str(msg),
+        }
+    }
 }
 
 impl Origin {
@@ -39,9 +109,7 @@ impl Origin {
     /// Errors are returned if
     ///
     /// * The argument cannot be parsed as an URL
-    /// * There's no host in the URL
-    /// * The URL scheme is not supported by the URL parser (rust-url)
-    /// * If there is no known default port for the scheme
+    /// * There's no host in the URL, and it's not a `file` URL
     ///
     /// #Examples
     /// ```
@@ -51,8 +119,28 @@ impl Origin {
     /// assert_eq!(o1, o2);
     /// ```
     pub fn parse(s: &str) -> Result<Origin, String> {
+        Origin::try_parse(s).map_err(|e| e.to_string())
+    }
+
+    /// Parses the given string as an origin, like `Origin::parse()`, but
+    /// classifies the reason of failure.
+    ///
+    /// #Examples
+    /// ```
+    /// use corsware::{Origin, ParseError};
+    /// assert!(Origin::try_parse("http://a.com").is_ok());
+    /// match Origin::try_parse("data:text/plain,hello") {
+    ///     Err(ParseError::Opaque(_)) => {}
+    ///     _ => panic!("data: URLs have opaque origins"),
+    /// }
+    /// match Origin::try_parse("][") {
+    ///     Err(ParseError::Malformed(_)) => {}
+    ///     _ => panic!("not an URL"),
+    /// }
+    /// ```
+    pub fn try_parse(s: &str) -> Result<Origin, ParseError> {
         match Url::parse(s) {
-            Err(_) => Err(format!("Could not be parsed as URL: '{}'", s)),
+            Err(_) => Err(ParseError::Malformed(format!("Could not be parsed as URL: '{}'", s))),
             Ok(url) => {
                 // - 1.  If the URI does not use a hierarchical element as a naming
                 // - authority (see [RFC3986], Section 3.2) or if the URI is not an
@@ -61,53 +149,57 @@ impl Origin {
                 //
                 // From https://hyper.rs/hyper/0.8.0/hyper/struct.Url.html#method.host:
                 // host(): If the URL is in a relative scheme, return its structured host.
-                match url.host_str() {
-                    None => Err(format!("No host in URL '{}'", url)),
-                    Some(host_str) => {
-                        // - 2. Let uri-scheme be the scheme component of the URI, converted to
-                        // - lowercase.
-                        let uri_scheme = url.scheme().to_owned().to_lowercase();
-
-
-                        //  4.  If uri-scheme is "file", the implementation MAY return an
-                        //  - implementation-defined value...
-                        // NOTE: file scheme not supported, would have bailed already
-
-                        // - 5. Let uri-host be the host component of the URI, converted to lower
-                        // - case (using the i;ascii-casemap collation defined in [RFC4790]).
-                        //
-                        // regarding i;ascii-casemap:
-                        // - Its equality, ordering, and substring operations are as for i;octet,
-                        // - except that at first, the lower-case letters (octet values 97-122) in
-                        // - each input string are changed to upper case (octet values 65-90).
-
-                        let uri_host = host_str.to_ascii_lowercase();
-
-                        // 6.  If there is no port component of the URI:
-                        //    1.  Let uri-port be the default port for the protocol given by
-                        //        uri-scheme.
-                        //        Otherwise:
-                        //    2.  Let uri-port be the port component of the URI.
-                        let uri_port = url.port_or_known_default();
-
-                        // - 3.  If the implementation doesn't support the protocol given by uri-
-                        // - scheme, then generate a fresh globally unique identifier and
-                        // - return that value.
-                        //
-                        // We support all schemes wich have a default port known by hyper
-                        match uri_port {
-                            None => Err(format!("Unsupported URL scheme	'{}'", uri_scheme)),
-                            Some(port) => {
-                                //   7.  Return the triple (uri-scheme, uri-host, uri-port).
-                                Ok(Origin::Triple {
-                                       scheme: uri_scheme,
-                                       host: uri_host,
-                                       port,
-                                   })
-                            }
-                        }
+                // - 2. Let uri-scheme be the scheme component of the URI, converted to
+                // - lowercase.
+                let uri_scheme = url.scheme().to_owned().to_lowercase();
+
+                //  4.  If uri-scheme is "file", the implementation MAY return an
+                //  - implementation-defined value...
+                //
+                // Like Electron, all file URLs are given the origin `file://`, with the
+                // host if there is one. Note that this makes all local files same-origin.
+                let host_str = match url.host_str() {
+                    None if uri_scheme == "file" => "",
+                    // From https://hyper.rs/hyper/0.8.0/hyper/struct.Url.html#method.host:
+                    // host(): If the URL is in a relative scheme, return its structured host.
+                    None | Some("") => {
+                        return Err(ParseError::Opaque(format!("No host in URL '{}'", url)))
                     }
-                }
+                    Some(host_str) => host_str,
+                };
+
+                // - 5. Let uri-host be the host component of the URI, converted to lower
+                // - case (using the i;ascii-casemap collation defined in [RFC4790]).
+                //
+                // regarding i;ascii-casemap:
+                // - Its equality, ordering, and substring operations are as for i;octet,
+                // - except that at first, the lower-case letters (octet values 97-122) in
+                // - each input string are changed to upper case (octet values 65-90).
+
+                let uri_host = host_str.to_ascii_lowercase();
+
+                // 6.  If there is no port component of the URI:
+                //    1.  Let uri-port be the default port for the protocol given by
+                //        uri-scheme.
+                //        Otherwise:
+                //    2.  Let uri-port be the port component of the URI.
+                //
+                // Schemes without default port, like `chrome-extension`, are left
+                // without port. Browsers serialize these origins without port as well.
+                let uri_port = url.port().or_else(|| default_port(&uri_scheme));
+
+                // - 3.  If the implementation doesn't support the protocol given by uri-
+                // - scheme, then generate a fresh globally unique identifier and
+                // - return that value.
+                //
+                // All schemes are supported, given a host.
+
+                //   7.  Return the triple (uri-scheme, uri-host, uri-port).
+                Ok(Origin::Triple {
+                       scheme: uri_scheme,
+                       host: uri_host,
+                       port: uri_port,
+                   })
             }
         }
     }
@@ -127,7 +219,7 @@ impl Origin {
     /// assert_eq!(o2, Ok(Origin::Triple {
     ///         scheme: "http".to_owned(),
     ///         host: "www.a.com".to_owned(),
-    ///         port: 80u16
+    ///         port: Some(80u16)
     ///         }));
     /// ```
     pub fn parse_allow_null(s: &str) -> Result<Origin, String> {
@@ -137,7 +229,57 @@ impl Origin {
         }
     }
 
+    /// Parses the given string as an origin, like `Origin::parse_allow_null()`,
+    /// but returns a fresh opaque origin for valid URLs which lack a `Triple`
+    /// origin. Only strings which are not URLs at all give errors.
+    ///
+    /// #Examples
+    /// ```
+    /// use corsware::Origin;
+    /// let o1 = Origin::parse_allow_opaque("data:text/plain,hello").unwrap();
+    /// let o2 = Origin::parse_allow_opaque("data:text/plain,hello").unwrap();
+    /// assert!(o1.is_opaque());
+    /// assert_ne!(o1, o2);
+    /// assert!(Origin::parse_allow_opaque("][").is_err());
+    /// ```
+    pub fn parse_allow_opaque(s: &str) -> Result<Origin, String> {
+        if s == "null" {
+            return Ok(Origin::Null);
+        }
+        match Origin::try_parse(s) {
+            Ok(origin) => Ok(origin),
+            Err(ParseError::Opaque(_)) => Ok(Origin::opaque()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// Returns a fresh opaque origin, which is not equal to any other origin.
+    pub fn opaque() -> Origin {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        Origin::Opaque(OpaqueId(NEXT_ID.fetch_add(1, Ordering::Relaxed)))
+    }
+
+    /// Returns true if this is an opaque origin.
+    pub fn is_opaque(&self) -> bool {
+        matches!(*self, Origin::Opaque(_))
+    }
+
+    /// Returns true if this is a `(scheme, host, port)` triple, i.e. neither
+    /// `Null` nor opaque. The accessors `scheme()`, `host()` and `port()` only
+    /// work on such origins.
+    /// #Example
+    /// ```
+    /// use corsware::Origin;
+    /// assert!(Origin::parse("http://a.com").unwrap().is_tuple());
+    /// assert!(!Origin::Null.is_tuple());
+    /// ```
+    pub fn is_tuple(&self) -> bool {
+        matches!(*self, Origin::Triple { .. })
+    }
+
     /// Returns the scheme of the origin in lower case.
+    /// #Panics
+    /// Panics for `Null` and opaque origins, see `is_tuple()` and `as_tuple()`.
     /// #Example
     /// ```
     /// use corsware::Origin;
@@ -146,11 +288,14 @@ impl Origin {
     pub fn scheme(&self) -> &String {
         match *self {
             Origin::Null => panic!("Null Origin has no scheme"),
+            Origin::Opaque(_) => panic!("Opaque Origin has no scheme"),
             Origin::Triple { ref scheme, .. } => scheme,
         }
     }
 
     /// Returns the host of the origin in ascii lower case.
+    /// #Panics
+    /// Panics for `Null` and opaque origins, see `is_tuple()` and `as_tuple()`.
     /// #Example
     /// ```
     /// use corsware::Origin;
@@ -159,24 +304,306 @@ impl Origin {
     pub fn host(&self) -> &String {
         match *self {
             Origin::Null => panic!("Null Origin has no host"),
+            Origin::Opaque(_) => panic!("Opaque Origin has no host"),
             Origin::Triple { ref host, .. } => host,
         }
     }
 
+    /// Returns the `(scheme, host, port)` triple of the origin, or `None` for
+    /// `Null` and opaque origins. Unlike the individual accessors, this never panics.
+    /// #Example
+    /// ```
+    /// use corsware::Origin;
+    /// assert_eq!(Origin::parse("https://a.com").unwrap().as_tuple(),
+    ///            Some(("https", "a.com", Some(443))));
+    /// assert_eq!(Origin::Null.as_tuple(), None);
+    /// ```
+    pub fn as_tuple(&self) -> Option<(&str, &str, Option<u16>)> {
+        match *self {
+            Origin::Null | Origin::Opaque(_) => None,
+            Origin::Triple { ref scheme, ref host, port } => Some((scheme, host, port)),
+        }
+    }
+
+    /// Serializes the origin according to the Unicode serialization in
+    /// RFC 6454 section 6.1, i.e. `scheme://host[:port]`, with the host converted
+    /// to Unicode and the port omitted if it's the default port of the scheme, including
+    /// ports registered through `Origin::register_default_port()`. `Null` and opaque
+    /// origins are serialized as `null`. Suitable for showing to humans.
+    ///
+    /// Only hosts of special schemes, such as `http`, are converted. The URL parser
+    /// doesn't punycode hosts of other schemes, such as `app`, so converting them
+    /// would give a different origin when parsed back.
+    /// #Example
+    /// ```
+    /// use corsware::Origin;
+    /// assert_eq!(Origin::parse("http://xn--a-1ga.com:8080").unwrap().unicode_serialization(),
+    ///            "http://aö.com:8080");
+    /// ```
+    pub fn unicode_serialization(&self) -> String {
+        match *self {
+            Origin::Null | Origin::Opaque(_) => self.serialize(""),
+            Origin::Triple { ref scheme, ref host, .. } => {
+                match Host::parse(host) {
+                    Host::Domain(ref domain) if is_special(scheme) => {
+                        match idna::domain_to_unicode(domain) {
+                            (unicode, Ok(())) => self.serialize(&unicode),
+                            _ => self.serialize(domain),
+                        }
+                    }
+                    _ => self.serialize(host),
+                }
+            }
+        }
+    }
+
+    /// Serializes the origin according to the ASCII serialization in
+    /// RFC 6454 section 6.2, i.e. `scheme://host[:port]`, with the host punycoded
+    /// and the port omitted if it's the default port of the scheme, including ports
+    /// registered through `Origin::register_default_port()`. `Null` and opaque origins
+    /// are serialized as `null`. This is the format used in the `Origin` header.
+    /// #Example
+    /// ```
+    /// use corsware::Origin;
+    /// assert_eq!(Origin::parse("http://aö.com:80").unwrap().ascii_serialization(),
+    ///            "http://xn--a-1ga.com");
+    /// ```
+    pub fn ascii_serialization(&self) -> String {
+        match *self {
+            Origin::Null | Origin::Opaque(_) => self.serialize(""),
+            Origin::Triple { ref host, .. } => self.serialize(host),
+        }
+    }
+
+    /// Serializes the origin with the given representation of its host
+    fn serialize(&self, host: &str) -> String {
+        match *self {
+            // - 1.  If the origin is not a scheme/host/port triple, then return the
+            // - string "null" (i.e., the code point sequence U+006E, U+0075, U+006C, U+006C)
+            // - and abort these steps.
+            Origin::Null | Origin::Opaque(_) => "null".to_owned(),
+            Origin::Triple { ref scheme, port, .. } => {
+                // - 2.  Otherwise, let result be the scheme part of the origin triple.
+                // - 3.  Append the string "://" to result.
+                // - 4.  Append each component of the host part of the origin triple
+                // - (converted as follows) to the result, separated by U+002E FULL STOP
+                // - code points ("."): ...
+                let mut result = format!("{}://{}", scheme, host);
+                // - 5.  If the port part of the origin triple is different from the
+                // - default port for the protocol given by the scheme part of the origin
+                // - triple:
+                // -     1.  Append a U+003A COLON code point (":") and the given port, in
+                // -         base ten, to result.
+                if let Some(port) = port {
+                    if default_port(scheme) != Some(port) {
+                        result.push_str(&format!(":{}", port));
+                    }
+                }
+                // - 6.  Return result.
+                result
+            }
+        }
+    }
+
+    /// Returns the host of the origin, classified as a domain or an IP address,
+    /// or `None` for `Null` and opaque origins.
+    /// #Example
+    /// ```
+    /// use corsware::{Origin, Host};
+    /// use std::net::Ipv4Addr;
+    /// assert_eq!(Origin::parse("http://10.20.30.40:8080").unwrap().typed_host(),
+    ///            Some(Host::Ipv4(Ipv4Addr::new(10, 20, 30, 40))));
+    /// assert_eq!(Origin::Null.typed_host(), None);
+    /// ```
+    pub fn typed_host(&self) -> Option<Host> {
+        match *self {
+            Origin::Null | Origin::Opaque(_) => None,
+            Origin::Triple { ref host, .. } => Some(Host::parse(host)),
+        }
+    }
+
+    /// Returns the registrable domain of the origin, also known as eTLD+1, as
+    /// determined by the embedded Public Suffix List. Returns `None` for the `Null`
+    /// origin, for IP address hosts and for hosts which are public suffixes themselves.
+    /// #Example
+    /// ```
+    /// use corsware::Origin;
+    /// assert_eq!(Origin::parse("https://www.example.co.uk").unwrap().registrable_domain(),
+    ///            Some("example.co.uk".to_owned()));
+    /// assert_eq!(Origin::parse("https://co.uk").unwrap().registrable_domain(), None);
+    /// ```
+    pub fn registrable_domain(&self) -> Option<String> {
+        match self.typed_host() {
+            Some(Host::Domain(domain)) => psl::registrable_domain(&domain),
+            Some(Host::Ipv4(_)) | Some(Host::Ipv6(_)) | None => None,
+        }
+    }
+
+    /// Returns true if this origin and `other` are schemeful same-site, as defined in
+    /// https://html.spec.whatwg.org/multipage/browsers.html#same-site. That is if the
+    /// schemes are equal and the hosts either are equal or share registrable domain.
+    /// Ports are not considered. An opaque origin is only same-site with itself, and
+    /// `Null` is never same-site with any origin, since it may stand for any opaque
+    /// origin.
+    /// #Example
+    /// ```
+    /// use corsware::Origin;
+    /// let o1 = Origin::parse("https://a.example.co.uk").unwrap();
+    /// let o2 = Origin::parse("https://b.example.co.uk:8443").unwrap();
+    /// let o3 = Origin::parse("https://other.co.uk").unwrap();
+    /// assert!(o1.is_same_site(&o2));
+    /// assert!(!o1.is_same_site(&o3));
+    /// ```
+    pub fn is_same_site(&self, other: &Origin) -> bool {
+        let (scheme, host, other_scheme, other_host) = match (self.as_tuple(),
+                                                              other.as_tuple()) {
+            (Some((scheme, host, _)), Some((other_scheme, other_host, _))) => {
+                (scheme, host, other_scheme, other_host)
+            }
+            _ => return self.is_opaque() && self == other,
+        };
+        if scheme != other_scheme {
+            return false;
+        }
+        if host == other_host {
+            return true;
+        }
+        match (self.registrable_domain(), other.registrable_domain()) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+
     /// Returns the port of the origin. Will return the default
-    /// port if not set explicitly
+    /// port if not set explicitly, or `None` if the scheme has no default port.
+    /// #Panics
+    /// Panics for `Null` and opaque origins, see `is_tuple()` and `as_tuple()`.
     /// #Example
     /// ```
     /// use corsware::Origin;
-    /// assert_eq!(Origin::parse("ftp://a.com").unwrap().port(), 21);
+    /// assert_eq!(Origin::parse("ftp://a.com").unwrap().port(), Some(21));
+    /// assert_eq!(Origin::parse("chrome-extension://abc").unwrap().port(), None);
     /// ```
-    pub fn port(&self) -> u16 {
+    pub fn port(&self) -> Option<u16> {
         match *self {
             Origin::Null => panic!("Null Origin has no port"),
-            Origin::Triple { ref port, .. } => *port,
+            Origin::Opaque(_) => panic!("Opaque Origin has no port"),
+            Origin::Triple { port, .. } => port,
         }
     }
+
+    /// Registers the default port of a scheme unknown to rust-url, so that
+    /// origins with the scheme and without port parsed from now on are given this
+    /// port. Origins which were already parsed are unaffected, so this should be done
+    /// before parsing any origin with the scheme, including the allowed origins.
+    /// Like the default ports known to rust-url, registered ports are omitted when
+    /// serializing, which also applies to origins parsed before registering. The
+    /// default ports of schemes known to rust-url can't be changed.
+    /// #Example
+    /// ```
+    /// use corsware::Origin;
+    /// Origin::register_default_port("myapp", 8443);
+    /// let origin = Origin::parse("myapp://a.com").unwrap();
+    /// assert_eq!(origin.port(), Some(8443));
+    /// assert_eq!(origin, Origin::parse("myapp://a.com:8443").unwrap());
+    /// assert_eq!(origin.to_string(), "myapp://a.com");
+    /// ```
+    pub fn register_default_port(scheme: &str, port: u16) {
+        registered_ports()
+            .write()
+            .unwrap()
+            .insert(scheme.to_ascii_lowercase(), port);
+    }
+
+    /// Returns true if the origin uses a secure scheme, that is `https` or `wss`.
+    /// The `Null` origin is never secure.
+    /// #Example
+    /// ```
+    /// use corsware::Origin;
+    /// assert!(Origin::parse("https://a.com").unwrap().is_secure());
+    /// assert!(!Origin::parse("http://a.com").unwrap().is_secure());
+    /// ```
+    pub fn is_secure(&self) -> bool {
+        match *self {
+            Origin::Null | Origin::Opaque(_) => false,
+            Origin::Triple { ref scheme, .. } => scheme == "https" || scheme == "wss",
+        }
+    }
+
+    /// Returns true if the host of the origin refers to the loopback interface,
+    /// i.e. `localhost`, an address in `127.0.0.0/8` or `[::1]`.
+    /// #Example
+    /// ```
+    /// use corsware::Origin;
+    /// assert!(Origin::parse("http://127.0.0.1:3000").unwrap().is_loopback());
+    /// assert!(!Origin::parse("http://a.com").unwrap().is_loopback());
+    /// ```
+    pub fn is_loopback(&self) -> bool {
+        match self.typed_host() {
+            Some(Host::Domain(domain)) => domain == "localhost",
+            Some(Host::Ipv4(ip)) => ip.is_loopback(),
+            Some(Host::Ipv6(ip)) => ip.is_loopback(),
+            None => false,
+        }
+    }
+}
+
+/// Default ports registered through `Origin::register_default_port()`
+fn registered_ports() -> &'static RwLock<HashMap<String, u16>> {
+    static PORTS: OnceLock<RwLock<HashMap<String, u16>>> = OnceLock::new();
+    PORTS.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+/// Returns true if the URL parser treats the scheme as special, i.e. parses
+/// its hosts as domains, punycoding them
+fn is_special(scheme: &str) -> bool {
+    matches!(scheme, "http" | "https" | "ws" | "wss" | "ftp" | "gopher" | "file")
+}
+
+/// Returns the default port of the given scheme, as known by rust-url
+fn known_default_port(scheme: &str) -> Option<u16> {
+    match scheme {
+        "http" | "ws" => Some(80),
+        "https" | "wss" => Some(443),
+        "ftp" => Some(21),
+        "gopher" => Some(70),
+        _ => None,
+    }
+}
+
+/// Returns the default port of the given scheme, as known by rust-url or
+/// registered through `Origin::register_default_port()`
+fn default_port(scheme: &str) -> Option<u16> {
+    known_default_port(scheme)
+        .or_else(|| registered_ports().read().unwrap().get(scheme).cloned())
+}
+
+/// Formats the origin according to its ASCII serialization, see
+/// `Origin::ascii_serialization()`.
+///
+/// #Example
+/// ```
+/// use corsware::Origin;
+/// assert_eq!(Origin::parse("hTtP://A.com:80/path").unwrap().to_string(), "http://a.com");
+/// assert_eq!(Origin::parse("http://a.com:8080").unwrap().to_string(), "http://a.com:8080");
+/// ```
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.ascii_serialization())
+    }
+}
+
+/// Parses an origin using `Origin::parse_allow_null()`, so that
+/// any formatted origin can be parsed back.
+impl FromStr for Origin {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Origin, String> {
+        Origin::parse_allow_null(s)
+    }
 }
 
 #[cfg(test)]
 mod tests;
+#[cfg(test)]
+mod serialization_tests;
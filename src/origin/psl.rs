@@ -0,0 +1,88 @@
+extern crate idna;
+
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+/// Snapshot of the Public Suffix List, https://publicsuffix.org/list/.
+/// Update by downloading a fresh copy over this file.
+static PUBLIC_SUFFIX_LIST: &str = include_str!("public_suffix_list.dat");
+
+/// The rules of the Public Suffix List, with all domains punycoded to
+/// match the hosts of parsed origins.
+struct Rules {
+    /// Plain rules, like `co.uk`
+    normal: HashSet<String>,
+    /// Wildcard rules, like `*.ck`, stored without the leading `*.`
+    wildcard: HashSet<String>,
+    /// Exception rules, like `!www.ck`, stored without the leading `!`
+    exception: HashSet<String>,
+}
+
+impl Rules {
+    fn parse(list: &str) -> Rules {
+        let mut rules = Rules {
+            normal: HashSet::new(),
+            wildcard: HashSet::new(),
+            exception: HashSet::new(),
+        };
+        // Each line is only read up to the first whitespace
+        for rule in list.lines().filter_map(|line| line.split_whitespace().next()) {
+            if rule.starts_with("//") {
+                continue;
+            }
+            let (set, domain) = if let Some(domain) = rule.strip_prefix('!') {
+                (&mut rules.exception, domain)
+            } else if let Some(domain) = rule.strip_prefix("*.") {
+                (&mut rules.wildcard, domain)
+            } else {
+                (&mut rules.normal, rule)
+            };
+            if let Ok(ascii) = idna::domain_to_ascii(domain) {
+                set.insert(ascii);
+            }
+        }
+        rules
+    }
+
+    /// Returns the number of labels in the public suffix of the given labels,
+    /// following the algorithm at https://publicsuffix.org/list/
+    fn suffix_len(&self, labels: &[&str]) -> usize {
+        let n = labels.len();
+        // Exception rules take priority over all other rules
+        for i in 0..n {
+            if self.exception.contains(&labels[i..].join(".")) {
+                return n - i - 1;
+            }
+        }
+        // Otherwise the matching rule with the most labels prevails
+        for i in 0..n {
+            if self.normal.contains(&labels[i..].join(".")) ||
+               (i + 1 < n && self.wildcard.contains(&labels[i + 1..].join("."))) {
+                return n - i;
+            }
+        }
+        // If no rules match, the prevailing rule is "*"
+        1
+    }
+}
+
+fn rules() -> &'static Rules {
+    static RULES: OnceLock<Rules> = OnceLock::new();
+    RULES.get_or_init(|| Rules::parse(PUBLIC_SUFFIX_LIST))
+}
+
+/// Returns the registrable domain, also known as eTLD+1, of the given punycoded
+/// domain. Returns `None` if the domain is a public suffix itself.
+pub fn registrable_domain(domain: &str) -> Option<String> {
+    let domain = domain.trim_end_matches('.');
+    let labels: Vec<&str> = domain.split('.').collect();
+    if labels.iter().any(|label| label.is_empty()) {
+        return None;
+    }
+    let suffix_len = rules().suffix_len(&labels);
+    if labels.len() <= suffix_len {
+        None
+    } else {
+        Some(labels[labels.len() - suffix_len - 1..].join("."))
+    }
+}
@@ -0,0 +1,84 @@
+extern crate quickcheck;
+
+use self::quickcheck::{quickcheck, Arbitrary, Gen};
+use super::Origin;
+
+/// Origins built from random schemes, hosts and ports. Hosts mix
+/// ascii, non-ascii and punycoded labels to exercise punycoding, also for
+/// non-special schemes, whose hosts are not punycoded by the URL parser.
+#[derive(Clone, Debug)]
+struct ArbitraryOrigin(Origin);
+
+impl Arbitrary for ArbitraryOrigin {
+    fn arbitrary(g: &mut Gen) -> ArbitraryOrigin {
+        if u8::arbitrary(g) % 10 == 0 {
+            return ArbitraryOrigin(Origin::Null);
+        }
+        let chars = ['a', 'b', 'z', 'A', 'Q', '0', '9', 'ä', 'ö', 'é', 'ñ', 'ж', '中', '日'];
+        loop {
+            let scheme = g.choose(&["http", "https", "ws", "wss", "ftp", "HtTp", "app"]).unwrap();
+            let labels: Vec<String> = (0..1 + usize::arbitrary(g) % 3)
+                .map(|_| if u8::arbitrary(g) % 4 == 0 {
+                         "xn--a-1ga".to_owned()
+                     } else {
+                         (0..1 + usize::arbitrary(g) % 8)
+                             .map(|_| *g.choose(&chars).unwrap())
+                             .collect()
+                     })
+                .collect();
+            let port = if bool::arbitrary(g) {
+                format!(":{}", u16::arbitrary(g))
+            } else {
+                "".to_owned()
+            };
+            let s = format!("{}://{}{}", scheme, labels.join("."), port);
+            if let Ok(origin) = Origin::parse(&s) {
+                return ArbitraryOrigin(origin);
+            }
+        }
+    }
+}
+
+#[test]
+fn ascii_serialization_round_trips() {
+    fn prop(o: ArbitraryOrigin) -> bool {
+        Origin::parse_allow_null(&o.0.ascii_serialization()) == Ok(o.0)
+    }
+    quickcheck(prop as fn(ArbitraryOrigin) -> bool);
+}
+
+#[test]
+fn unicode_serialization_round_trips() {
+    fn prop(o: ArbitraryOrigin) -> bool {
+        Origin::parse_allow_null(&o.0.unicode_serialization()) == Ok(o.0)
+    }
+    quickcheck(prop as fn(ArbitraryOrigin) -> bool);
+}
+
+#[test]
+fn ascii_serialization_is_ascii() {
+    fn prop(o: ArbitraryOrigin) -> bool {
+        o.0.ascii_serialization().is_ascii()
+    }
+    quickcheck(prop as fn(ArbitraryOrigin) -> bool);
+}
+
+#[test]
+fn null_is_serialized_as_null() {
+    assert_eq!(Origin::Null.ascii_serialization(), "null");
+    assert_eq!(Origin::Null.unicode_serialization(), "null");
+}
+
+#[test]
+fn unicode_serialization_decodes_punycode() {
+    let o = Origin::parse("https://xn--a-1ga.xn--55qx5d.cn:8443").unwrap();
+    assert_eq!(o.unicode_serialization(), "https://aö.公司.cn:8443");
+    assert_eq!(o.ascii_serialization(), "https://xn--a-1ga.xn--55qx5d.cn:8443");
+}
+
+#[test]
+fn unicode_serialization_keeps_hosts_of_non_special_schemes() {
+    let o = Origin::parse("app://xn--a-1ga").unwrap();
+    assert_eq!(o.unicode_serialization(), "app://xn--a-1ga");
+    assert_eq!(Origin::parse(&o.unicode_serialization()), Ok(o));
+}
@@ -0,0 +1,494 @@
+//! Reporting of rejected CORS requests, see `ViolationReporter`, and
+//! report-only evaluation of a candidate policy, see `CandidatePolicy`.
+
+use http::Method;
+#[cfg(feature = "hyper")]
+use hyper::{Client, Url};
+#[cfg(feature = "hyper")]
+use hyper::header::ContentType;
+use unicase::UniCase;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "hyper")]
+use std::net::{TcpStream, ToSocketAddrs};
+#[cfg(feature = "hyper")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "hyper")]
+use std::sync::mpsc::{self, Sender, SyncSender, Receiver, RecvTimeoutError, TrySendError};
+#[cfg(feature = "hyper")]
+use std::thread;
+#[cfg(feature = "hyper")]
+use std::time::{Duration, Instant};
+use std::time::{SystemTime, UNIX_EPOCH};
+use AllowedOrigins;
+
+/// What a request was rejected for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ViolationKind {
+    /// A preflight request without Origin header
+    MissingOrigin,
+    /// The request carries several different origins
+    MultipleOrigins,
+    /// The Origin header is malformed
+    MalformedOrigin,
+    /// The origin isn't allowed
+    Origin,
+    /// The origin is insecure, and credentials are only allowed for secure origins
+    InsecureOrigin,
+    /// The requested method isn't allowed
+    Method,
+    /// Some of the requested headers aren't allowed
+    Headers,
+}
+
+impl fmt::Display for ViolationKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            ViolationKind::MissingOrigin => "missing origin",
+            ViolationKind::MultipleOrigins => "multiple origins",
+            ViolationKind::MalformedOrigin => "malformed origin",
+            ViolationKind::Origin => "disallowed origin",
+            ViolationKind::InsecureOrigin => "insecure origin",
+            ViolationKind::Method => "disallowed method",
+            ViolationKind::Headers => "disallowed headers",
+        })
+    }
+}
+
+/// A CORS request which a policy rejects
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    /// Whether the request is a preflight request
+    pub preflight: bool,
+    /// The Origin header of the request. Empty for preflight requests
+    /// without Origin header.
+    pub origin: String,
+    /// The requested method, i.e. Access-Control-Request-Method for
+    /// preflight requests and the method of the request otherwise
+    pub method: Method,
+    /// The requested headers, i.e. Access-Control-Request-Headers for
+    /// preflight requests. Empty for normal requests.
+    pub headers: Vec<UniCase<String>>,
+    /// What the request was rejected for
+    pub kind: ViolationKind,
+}
+
+/// Receives the violations of a `CandidatePolicy`
+pub trait ViolationSink: Send + Sync {
+    /// Called once for each request violating the policy
+    fn report(&self, violation: Violation);
+}
+
+impl<F> ViolationSink for F
+    where F: Fn(Violation) + Send + Sync
+{
+    fn report(&self, violation: Violation) {
+        self(violation)
+    }
+}
+
+/// A violation together with where and when it happened, as passed to a
+/// `ViolationReporter`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ViolationReport {
+    /// When the request was rejected
+    pub timestamp: SystemTime,
+    /// The name of the rejecting policy: `enforced` for the policy of the
+    /// middleware, `report-only` for its `CandidatePolicy`
+    pub policy: String,
+    /// The path of the request
+    pub path: String,
+    /// The rejected request
+    pub violation: Violation,
+}
+
+impl ViolationReport {
+    /// Serializes the report as a JSON object, such as
+    /// `{"timestamp":1500000000000,"policy":"enforced","preflight":true,
+    /// "origin":"http://a.com","method":"PUT","headers":["X-Custom"],
+    /// "path":"/a","reason":"disallowed headers"}`. The timestamp is in
+    /// milliseconds since the epoch, and a missing origin is `null`.
+    pub fn to_json(&self) -> String {
+        let timestamp = self.timestamp
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() * 1000 + u64::from(d.subsec_millis()))
+            .unwrap_or(0);
+        let violation = &self.violation;
+        let origin = if violation.origin.is_empty() {
+            "null".to_owned()
+        } else {
+            json_string(&violation.origin)
+        };
+        let headers = violation.headers
+            .iter()
+            .map(|h| json_string(h))
+            .collect::<Vec<_>>()
+            .join(",");
+        format!("{{\"timestamp\":{},\"policy\":{},\"preflight\":{},\"origin\":{},\
+                 \"method\":{},\"headers\":[{}],\"path\":{},\"reason\":{}}}",
+                timestamp,
+                json_string(&self.policy),
+                violation.preflight,
+                origin,
+                json_string(violation.method.as_ref()),
+                headers,
+                json_string(&self.path),
+                json_string(&violation.kind.to_string()))
+    }
+}
+
+/// Quotes and escapes a string as a JSON string
+pub(crate) fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Receives reports of violations of a CORS policy, see
+/// `CorsMiddleware::violation_reporter`. Reporting happens on the request
+/// thread, so implementations should be quick.
+pub trait ViolationReporter: Send + Sync {
+    /// Called once for each request violating the policy
+    fn report(&self, report: &ViolationReport);
+}
+
+impl<F> ViolationReporter for F
+    where F: Fn(&ViolationReport) + Send + Sync
+{
+    fn report(&self, report: &ViolationReport) {
+        self(report)
+    }
+}
+
+/// Appends violations to a file, one JSON object per line
+pub struct JsonLinesReporter {
+    file: Mutex<File>,
+}
+
+impl JsonLinesReporter {
+    /// Opens the given file for appending, creating it if needed
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<JsonLinesReporter> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(JsonLinesReporter { file: Mutex::new(file) })
+    }
+}
+
+impl ViolationReporter for JsonLinesReporter {
+    fn report(&self, report: &ViolationReport) {
+        let line = format!("{}\n", report.to_json());
+        // Reporting must not fail the request, so write errors are ignored
+        if let Ok(mut file) = self.file.lock() {
+            let _ = file.write_all(line.as_bytes());
+        }
+    }
+}
+
+/// Keeps the latest reports in memory, dropping the oldest ones when full
+pub struct RingBufferReporter {
+    capacity: usize,
+    reports: Mutex<VecDeque<ViolationReport>>,
+}
+
+impl RingBufferReporter {
+    /// New reporter keeping at most `capacity` reports
+    pub fn new(capacity: usize) -> RingBufferReporter {
+        RingBufferReporter {
+            capacity,
+            reports: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Returns the kept reports, oldest first
+    pub fn reports(&self) -> Vec<ViolationReport> {
+        self.reports.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+impl ViolationReporter for RingBufferReporter {
+    fn report(&self, report: &ViolationReport) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut reports = self.reports.lock().unwrap();
+        if reports.len() == self.capacity {
+            reports.pop_front();
+        }
+        reports.push_back(report.clone());
+    }
+}
+
+/// Sends violation reports in batches to a URL, as a JSON array in the body
+/// of a `POST` request with content type `application/reports+json`.
+///
+/// Sending happens on a background thread. A batch is sent once it holds
+/// `batch_size` reports, or when `max_delay` has passed since its first
+/// report. Use `flush()` to send the current batch right away, such as on
+/// shutdown. Failed requests are not retried.
+///
+/// At most 1024 reports are queued for the worker thread. Reports
+/// received while the queue is full, such as when the collector is slow, are
+/// dropped and counted, see `dropped()`.
+///
+/// Only `http` URLs are supported, since the reporter doesn't do TLS. Use a
+/// collector reachable over plain HTTP, such as on the same host or through
+/// a local proxy.
+///
+/// Connecting, sending and waiting for the response each time out after
+/// `timeout`, 5 seconds unless created with `with_timeout()`. Dropping the
+/// reporter sends what's left, waiting for at most `timeout` before leaving
+/// the worker thread to finish on its own.
+///
+/// Requires the `hyper` feature, since it's built on the hyper client.
+#[cfg(feature = "hyper")]
+pub struct HttpReporter {
+    sender: Mutex<Option<SyncSender<Message>>>,
+    dropped: AtomicUsize,
+    // Disconnected when the worker thread exits
+    finished: Mutex<Receiver<()>>,
+    timeout: Duration,
+}
+
+/// The number of reports an `HttpReporter` queues for its worker thread
+#[cfg(feature = "hyper")]
+const QUEUE_CAPACITY: usize = 1024;
+
+/// Messages to the worker thread of an `HttpReporter`
+#[cfg(feature = "hyper")]
+enum Message {
+    Report(ViolationReport),
+    Flush(Sender<()>),
+}
+
+#[cfg(feature = "hyper")]
+impl HttpReporter {
+    /// New reporter sending batches to the given URL, with a timeout of 5
+    /// seconds.
+    /// #Errors
+    /// Errors with `InvalidInput` if the URL can't be parsed or isn't an `http` URL.
+    pub fn new(url: &str, batch_size: usize, max_delay: Duration) -> io::Result<HttpReporter> {
+        HttpReporter::with_timeout(url, batch_size, max_delay, Duration::from_secs(5))
+    }
+
+    /// New reporter sending batches to the given URL, with the given timeout.
+    /// #Errors
+    /// Errors with `InvalidInput` if the URL can't be parsed or isn't an `http` URL.
+    pub fn with_timeout(url: &str,
+                        batch_size: usize,
+                        max_delay: Duration,
+                        timeout: Duration)
+                        -> io::Result<HttpReporter> {
+        let url = match Url::parse(url) {
+            Ok(ref url) if url.scheme() != "http" => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          format!("Only http URLs are supported: '{}'", url)))
+            }
+            Ok(url) => url,
+            Err(e) => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          format!("Invalid URL '{}': {}", url, e)))
+            }
+        };
+        let (sender, receiver) = mpsc::sync_channel::<Message>(QUEUE_CAPACITY);
+        let (finished_sender, finished) = mpsc::channel::<()>();
+        let batch_size = batch_size.max(1);
+        thread::spawn(move || {
+            let _finished = finished_sender;
+            // Batches are infrequent, so connections aren't pooled
+            let mut client = Client::with_connector(move |host: &str, port: u16, scheme: &str| {
+                connect_timeout(host, port, scheme, timeout)
+            });
+            client.set_read_timeout(Some(timeout));
+            client.set_write_timeout(Some(timeout));
+            let send = |batch: &mut Vec<ViolationReport>| {
+                if batch.is_empty() {
+                    return;
+                }
+                let body = format!("[{}]",
+                                   batch.iter()
+                                       .map(|r| r.to_json())
+                                       .collect::<Vec<_>>()
+                                       .join(","));
+                let _ = client.post(url.clone())
+                    .header(ContentType("application/reports+json".parse().unwrap()))
+                    .body(&body)
+                    .send();
+                batch.clear();
+            };
+            let mut batch = vec![];
+            let mut deadline: Option<Instant> = None;
+            loop {
+                let received = match deadline {
+                    None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                    Some(deadline) => {
+                        receiver.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                    }
+                };
+                match received {
+                    Ok(Message::Report(report)) => {
+                        if batch.is_empty() {
+                            deadline = Some(Instant::now() + max_delay);
+                        }
+                        batch.push(report);
+                        if batch.len() >= batch_size {
+                            send(&mut batch);
+                            deadline = None;
+                        }
+                    }
+                    Ok(Message::Flush(done)) => {
+                        send(&mut batch);
+                        deadline = None;
+                        let _ = done.send(());
+                    }
+                    Err(RecvTimeoutError::Timeout) => {
+                        send(&mut batch);
+                        deadline = None;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => {
+                        send(&mut batch);
+                        return;
+                    }
+                }
+            }
+        });
+        Ok(HttpReporter {
+            sender: Mutex::new(Some(sender)),
+            dropped: AtomicUsize::new(0),
+            finished: Mutex::new(finished),
+            timeout,
+        })
+    }
+
+    /// Sends the reports received so far, blocking until done
+    pub fn flush(&self) {
+        let (done, wait) = mpsc::channel();
+        if let Some(ref sender) = *self.sender.lock().unwrap() {
+            if sender.send(Message::Flush(done)).is_ok() {
+                let _ = wait.recv();
+            }
+        }
+    }
+
+    /// Returns the number of reports dropped so far since the queue was full
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Connects like hyper's `HttpConnector`, giving up after `timeout`
+#[cfg(feature = "hyper")]
+fn connect_timeout(host: &str,
+                   port: u16,
+                   scheme: &str,
+                   timeout: Duration)
+                   -> io::Result<TcpStream> {
+    if scheme != "http" {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid scheme for Http"));
+    }
+    let mut error = io::Error::new(io::ErrorKind::NotFound, "No address for host");
+    for addr in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => error = e,
+        }
+    }
+    Err(error)
+}
+
+#[cfg(feature = "hyper")]
+impl ViolationReporter for HttpReporter {
+    fn report(&self, report: &ViolationReport) {
+        if let Some(ref sender) = *self.sender.lock().unwrap() {
+            // Reporting must not block the request, so reports are dropped when
+            // the worker falls behind
+            if let Err(TrySendError::Full(_)) = sender.try_send(Message::Report(report.clone())) {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "hyper")]
+impl Drop for HttpReporter {
+    fn drop(&mut self) {
+        // Closing the channel makes the worker send what's left and exit
+        self.sender.lock().unwrap().take();
+        if let Ok(finished) = self.finished.lock() {
+            let _ = finished.recv_timeout(self.timeout);
+        }
+    }
+}
+
+/// A stricter policy evaluated alongside the enforced one, without being
+/// applied, analogous to `Content-Security-Policy-Report-Only`. Requests it
+/// would reject are reported to the sink, so that it can be rolled out
+/// without breaking anyone unnoticed. See `CorsMiddleware::report_only`.
+///
+/// The origin is checked for all CORS requests, while methods and headers are
+/// only checked for preflight requests, just like the enforced policy.
+#[derive(Clone)]
+pub struct CandidatePolicy {
+    /// The origins the candidate policy would allow
+    pub allowed_origins: AllowedOrigins,
+    /// The methods the candidate policy would allow
+    pub allowed_methods: Vec<Method>,
+    /// The headers the candidate policy would allow
+    pub allowed_headers: Vec<UniCase<String>>,
+    /// Where violations are reported
+    pub sink: Arc<dyn ViolationSink>,
+}
+
+impl CandidatePolicy {
+    /// Returns what the candidate policy would reject the request for, if anything
+    fn check(&self,
+             origin: &str,
+             method: &Method,
+             headers: &[UniCase<String>],
+             preflight: bool)
+             -> Option<ViolationKind> {
+        if self.allowed_origins.allowed_for(origin, false, false).is_none() {
+            Some(ViolationKind::Origin)
+        } else if preflight && !self.allowed_methods.contains(method) {
+            Some(ViolationKind::Method)
+        } else if preflight && !headers.iter().all(|h| self.allowed_headers.contains(h)) {
+            Some(ViolationKind::Headers)
+        } else {
+            None
+        }
+    }
+
+    /// Evaluates the candidate policy for a request, reporting it to the sink
+    /// if it would be rejected. Returns the reported violation.
+    pub(crate) fn evaluate(&self,
+                           origin: &str,
+                           method: &Method,
+                           headers: &[UniCase<String>],
+                           preflight: bool)
+                           -> Option<Violation> {
+        let kind = self.check(origin, method, headers, preflight)?;
+        let violation = Violation {
+            preflight,
+            origin: origin.to_owned(),
+            method: method.clone(),
+            headers: headers.to_vec(),
+            kind,
+        };
+        self.sink.report(violation.clone());
+        Some(violation)
+    }
+}
@@ -1,3 +1,7 @@
+#![cfg(feature = "iron")]
+
+#![allow(dead_code)]
+
 extern crate iron;
 extern crate hyper;
 extern crate router;
@@ -53,7 +57,7 @@ impl AutoServer {
 
     pub fn with_handler<H: Handler>(handler: H) -> AutoServer {
         let i = Iron {
-            handler: handler,
+            handler,
             timeouts: Timeouts { keep_alive: None, .. Timeouts::default()},
             threads: 1
         };
@@ -1,3 +1,5 @@
+#![cfg(feature = "iron")]
+
 /// Test fixture for browser tests
 extern crate iron;
 extern crate unicase;
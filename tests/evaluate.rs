@@ -0,0 +1,238 @@
+extern crate corsware;
+extern crate http;
+extern crate unicase;
+use http::{HeaderMap, HeaderValue, Method};
+use corsware::{CorsMiddleware, AllowedOrigins, Origin, CorsRequest, CorsOutcome, CorsHeaders,
+               CorsRejection, ViolationKind, RingBufferReporter, ResourcePolicy};
+use std::collections::HashSet;
+use std::sync::Arc;
+use unicase::UniCase;
+
+fn restrictive() -> CorsMiddleware {
+    let mut origins = HashSet::new();
+    origins.insert(Origin::parse("https://www.a.com").unwrap());
+    CorsMiddleware {
+        allowed_origins: AllowedOrigins::Specific(origins),
+        allowed_methods: vec![Method::GET, Method::PUT],
+        allowed_headers: vec![UniCase("X-Custom".to_owned())],
+        exposed_headers: vec![UniCase("X-Exposed".to_owned())],
+        allow_credentials: true,
+        max_age_seconds: 60,
+        ..CorsMiddleware::permissive()
+    }
+}
+
+fn preflight(origin: &str, method: Method, headers: &[&str]) -> CorsRequest {
+    CorsRequest {
+        method: Method::OPTIONS,
+        origin: Some(origin.to_owned()),
+        requested_method: Some(method),
+        requested_headers: headers.iter().map(|h| UniCase(h.to_string())).collect(),
+    }
+}
+
+fn normal(origin: Option<&str>) -> CorsRequest {
+    CorsRequest {
+        method: Method::GET,
+        origin: origin.map(|o| o.to_owned()),
+        requested_method: None,
+        requested_headers: vec![],
+    }
+}
+
+fn rejection(outcome: CorsOutcome) -> CorsRejection {
+    match outcome {
+        CorsOutcome::Rejected(rejection) => rejection,
+        other => panic!("expected rejection, got {:?}", other),
+    }
+}
+
+#[test]
+fn allowed_preflight_gets_exact_headers() {
+    let req = preflight("https://www.a.com", Method::PUT, &["x-custom"]);
+    match restrictive().evaluate(&req) {
+        CorsOutcome::Preflight(headers) => {
+            assert_eq!(headers.to_pairs(),
+                       vec![("Access-Control-Allow-Credentials", "true".to_owned()),
+                            ("Access-Control-Allow-Origin", "https://www.a.com".to_owned()),
+                            ("Access-Control-Max-Age", "60".to_owned()),
+                            ("Access-Control-Allow-Methods", "GET, PUT".to_owned()),
+                            ("Access-Control-Allow-Headers", "X-Custom".to_owned())]);
+        }
+        other => panic!("expected preflight, got {:?}", other),
+    }
+}
+
+#[test]
+fn allowed_normal_request_gets_exposed_headers() {
+    assert_eq!(restrictive().evaluate(&normal(Some("https://www.a.com"))),
+               CorsOutcome::Normal(CorsHeaders {
+                   allow_origin: "https://www.a.com".to_owned(),
+                   allow_credentials: true,
+                   max_age: None,
+                   allow_methods: vec![],
+                   allow_headers: vec![],
+                   expose_headers: vec![UniCase("X-Exposed".to_owned())],
+                   timing_allow_origin: None,
+               }));
+}
+
+#[test]
+fn options_without_request_method_is_normal() {
+    let req = CorsRequest { method: Method::OPTIONS, ..normal(Some("https://www.a.com")) };
+    assert!(!req.is_preflight());
+    match restrictive().evaluate(&req) {
+        CorsOutcome::Normal(_) => {}
+        other => panic!("expected normal, got {:?}", other),
+    }
+}
+
+#[test]
+fn normal_request_without_origin_is_not_cors() {
+    assert_eq!(restrictive().evaluate(&normal(None)), CorsOutcome::NotCors);
+}
+
+#[test]
+fn preflight_without_origin_is_rejected() {
+    let req = CorsRequest { origin: None, ..preflight("", Method::GET, &[]) };
+    let rejection = rejection(restrictive().evaluate(&req));
+    assert!(rejection.preflight);
+    assert_eq!(rejection.kind, ViolationKind::MissingOrigin);
+    assert_eq!(rejection.message, "Preflight request without Origin header");
+}
+
+#[test]
+fn multiple_origins_are_rejected() {
+    let req = normal(Some("https://www.a.com https://www.b.com"));
+    let rejection = rejection(restrictive().evaluate(&req));
+    assert!(!rejection.preflight);
+    assert_eq!(rejection.kind, ViolationKind::MultipleOrigins);
+    assert_eq!(rejection.message, "Normal request with multiple origins");
+}
+
+#[test]
+fn repeated_identical_origins_are_collapsed() {
+    let req = normal(Some("https://www.a.com https://www.a.com"));
+    match restrictive().evaluate(&req) {
+        CorsOutcome::Normal(headers) => assert_eq!(headers.allow_origin, "https://www.a.com"),
+        other => panic!("expected normal, got {:?}", other),
+    }
+}
+
+#[test]
+fn opaque_origin_is_allowed_if_opaque_origins_are() {
+    let cors = CorsMiddleware {
+        allowed_origins: AllowedOrigins::Any {
+            allow_null: false,
+            allow_opaque: true,
+        },
+        ..restrictive()
+    };
+    match cors.evaluate(&normal(Some("data:text/plain,hi"))) {
+        CorsOutcome::Normal(headers) => assert_eq!(headers.allow_origin, "data:text/plain,hi"),
+        other => panic!("expected normal, got {:?}", other),
+    }
+    let rejection = rejection(restrictive().evaluate(&normal(Some("data:text/plain,hi"))));
+    assert_eq!(rejection.kind, ViolationKind::MalformedOrigin);
+}
+
+#[test]
+fn malformed_origin_is_rejected() {
+    let rejection = rejection(restrictive().evaluate(&normal(Some("https://www.a.com/"))));
+    assert_eq!(rejection.kind, ViolationKind::MalformedOrigin);
+}
+
+#[test]
+fn disallowed_origin_is_rejected() {
+    let rejection = rejection(restrictive()
+        .evaluate(&preflight("https://www.b.com", Method::GET, &[])));
+    assert_eq!(rejection.kind, ViolationKind::Origin);
+    assert_eq!(rejection.message,
+               "Preflight request requesting disallowed origin 'https://www.b.com'");
+}
+
+#[test]
+fn disallowed_method_is_rejected() {
+    let rejection = rejection(restrictive()
+        .evaluate(&preflight("https://www.a.com", Method::DELETE, &[])));
+    assert_eq!(rejection.kind, ViolationKind::Method);
+    assert_eq!(rejection.message, "Preflight request requesting disallowed method DELETE");
+}
+
+#[test]
+fn disallowed_headers_are_listed_once_in_request_order() {
+    let req = preflight("https://www.a.com",
+                        Method::GET,
+                        &["X-B", "x-custom", "X-A", "x-b"]);
+    let rejection = rejection(restrictive().evaluate(&req));
+    assert_eq!(rejection.kind, ViolationKind::Headers);
+    assert_eq!(rejection.message,
+               "Preflight request requesting disallowed header(s) X-B,X-A");
+}
+
+#[test]
+fn request_is_extracted_from_headers() {
+    let mut headers = HeaderMap::new();
+    headers.append("Origin", HeaderValue::from_static("https://www.a.com"));
+    headers.append("Origin", HeaderValue::from_static("https://www.b.com"));
+    headers.append("Access-Control-Request-Method", HeaderValue::from_static("PUT"));
+    headers.append("Access-Control-Request-Headers", HeaderValue::from_static("X-A, x-b"));
+    headers.append("Access-Control-Request-Headers", HeaderValue::from_static("X-C"));
+    assert_eq!(CorsRequest::from_headers(Method::OPTIONS, &headers),
+               CorsRequest {
+                   method: Method::OPTIONS,
+                   origin: Some("https://www.a.com https://www.b.com".to_owned()),
+                   requested_method: Some(Method::PUT),
+                   requested_headers: vec![UniCase("X-A".to_owned()),
+                                           UniCase("X-B".to_owned()),
+                                           UniCase("X-C".to_owned())],
+               });
+}
+
+#[test]
+fn invalid_request_method_is_not_preflight() {
+    let mut headers = HeaderMap::new();
+    headers.insert("Origin", HeaderValue::from_static("https://www.a.com"));
+    headers.insert("Access-Control-Request-Method", HeaderValue::from_static("P(T"));
+    let req = CorsRequest::from_headers(Method::OPTIONS, &headers);
+    assert_eq!(req.requested_method, None);
+    assert!(!req.is_preflight());
+}
+
+#[test]
+fn headers_convert_to_header_map() {
+    let req = preflight("https://www.a.com", Method::GET, &[]);
+    let map = match restrictive().evaluate(&req) {
+        CorsOutcome::Preflight(headers) => headers.to_header_map(),
+        other => panic!("expected preflight, got {:?}", other),
+    };
+    assert_eq!(map["access-control-allow-origin"], "https://www.a.com");
+    assert_eq!(map["access-control-allow-methods"], "GET, PUT");
+    assert_eq!(map.len(), 5);
+}
+
+#[test]
+fn rejections_are_reported_by_process() {
+    let reporter = Arc::new(RingBufferReporter::new(10));
+    let cors = CorsMiddleware { violation_reporter: Some(reporter.clone()), ..restrictive() };
+    let req = preflight("https://www.b.com", Method::GET, &[]);
+    assert_eq!(cors.process(&req, "/a"), cors.evaluate(&req));
+    let reports = reporter.reports();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].violation.kind, ViolationKind::Origin);
+    assert_eq!(reports[0].path, "/a");
+    assert_eq!(reports[0].violation.origin, "https://www.b.com");
+}
+
+#[test]
+fn response_headers_include_vary_and_resource_policy() {
+    let cors = CorsMiddleware {
+        resource_policy: ResourcePolicy::FromAllowedOrigins,
+        ..restrictive()
+    };
+    assert_eq!(cors.response_headers(&normal(None)),
+               vec![("Vary".to_owned(),
+                     "Origin, Access-Control-Request-Method, Access-Control-Request-Headers"
+                         .to_owned()),
+                    ("Cross-Origin-Resource-Policy".to_owned(), "same-site".to_owned())]);
+}
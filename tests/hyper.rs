@@ -0,0 +1,188 @@
+#![cfg(feature = "hyper")]
+
+extern crate corsware;
+extern crate http;
+extern crate hyper;
+extern crate unicase;
+use corsware::{CorsMiddleware, CorsHandler, AllowedOrigins, Origin, OriginHeader};
+use hyper::Client;
+use hyper::header::{Headers, AccessControlRequestMethod, AccessControlAllowOrigin,
+                    AccessControlAllowMethods, AccessControlExposeHeaders, Vary, Upgrade,
+                    Protocol, ProtocolName, Connection, ConnectionOption};
+use hyper::method::Method;
+use hyper::server::{Server, Listening, Request, Response};
+use hyper::status::StatusCode;
+use std::collections::HashSet;
+use std::io::Read;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use unicase::UniCase;
+
+fn restrictive() -> CorsMiddleware {
+    let mut origins = HashSet::new();
+    origins.insert(Origin::parse("https://www.a.com").unwrap());
+    CorsMiddleware {
+        allowed_origins: AllowedOrigins::Specific(origins),
+        allowed_methods: vec![http::Method::GET, http::Method::PUT],
+        exposed_headers: vec![UniCase("X-Exposed".to_owned())],
+        ..CorsMiddleware::permissive()
+    }
+}
+
+struct TestServer {
+    listening: Listening,
+    calls: Arc<AtomicUsize>,
+}
+
+impl TestServer {
+    /// Starts a server with a `CorsHandler` wrapping a handler which adds
+    /// `Accept-Encoding` to `Vary` and responds with `418 I'm a teapot`
+    fn new(cors: CorsMiddleware) -> TestServer {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let handler = move |_: Request, mut res: Response| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let mut vary = match res.headers().get::<Vary>() {
+                Some(Vary::Items(items)) => items.clone(),
+                _ => vec![],
+            };
+            vary.push(UniCase("Accept-Encoding".to_owned()));
+            res.headers_mut().set(Vary::Items(vary));
+            *res.status_mut() = StatusCode::ImATeapot;
+            res.send(b"Hello world!").unwrap();
+        };
+        let mut server = Server::http("127.0.0.1:0").unwrap();
+        server.keep_alive(None);
+        let listening = server.handle_threads(CorsHandler::new(cors, handler), 1).unwrap();
+        TestServer { listening, calls }
+    }
+
+    fn send(&self, method: Method, headers: Headers) -> (hyper::client::Response, String) {
+        let url = format!("http://127.0.0.1:{}/a?b=c", self.listening.socket.port());
+        let mut res = Client::new().request(method, &url).headers(headers).send().unwrap();
+        let mut body = String::new();
+        res.read_to_string(&mut body).unwrap();
+        (res, body)
+    }
+
+    fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.listening.close().unwrap();
+    }
+}
+
+fn origin(origin: &str) -> Headers {
+    let mut headers = Headers::new();
+    headers.set(OriginHeader(origin.to_owned()));
+    headers
+}
+
+fn preflight(origin_value: &str, method: Method) -> Headers {
+    let mut headers = origin(origin_value);
+    headers.set(AccessControlRequestMethod(method));
+    headers
+}
+
+fn cors_vary() -> Vary {
+    Vary::Items(vec![UniCase("Origin".to_owned()),
+                     UniCase("Access-Control-Request-Method".to_owned()),
+                     UniCase("Access-Control-Request-Headers".to_owned())])
+}
+
+#[test]
+fn preflight_is_answered_without_calling_handler() {
+    let server = TestServer::new(restrictive());
+    let (res, body) = server.send(Method::Options, preflight("https://www.a.com", Method::Put));
+    assert_eq!(server.calls(), 0);
+    assert_eq!(res.status, StatusCode::NoContent);
+    assert_eq!(res.headers.get::<AccessControlAllowOrigin>(),
+               Some(&AccessControlAllowOrigin::Value("https://www.a.com".to_owned())));
+    assert_eq!(res.headers.get::<AccessControlAllowMethods>(),
+               Some(&AccessControlAllowMethods(vec![Method::Get, Method::Put])));
+    assert_eq!(res.headers.get::<Vary>(), Some(&cors_vary()));
+    assert_eq!(body, "");
+}
+
+#[test]
+fn disallowed_preflight_is_rejected_without_calling_handler() {
+    let server = TestServer::new(restrictive());
+    let (res, body) = server.send(Method::Options,
+                                  preflight("https://www.a.com", Method::Delete));
+    assert_eq!(server.calls(), 0);
+    assert_eq!(res.status, StatusCode::BadRequest);
+    assert_eq!(body, "Preflight request requesting disallowed method DELETE");
+    assert!(res.headers.get::<AccessControlAllowOrigin>().is_none());
+    assert_eq!(res.headers.get::<Vary>(), Some(&cors_vary()));
+}
+
+#[test]
+fn normal_response_is_decorated() {
+    let server = TestServer::new(restrictive());
+    let (res, body) = server.send(Method::Get, origin("https://www.a.com"));
+    assert_eq!(server.calls(), 1);
+    assert_eq!(res.status, StatusCode::ImATeapot);
+    assert_eq!(body, "Hello world!");
+    assert_eq!(res.headers.get::<AccessControlAllowOrigin>(),
+               Some(&AccessControlAllowOrigin::Value("https://www.a.com".to_owned())));
+    assert_eq!(res.headers.get::<AccessControlExposeHeaders>(),
+               Some(&AccessControlExposeHeaders(vec![UniCase("X-Exposed".to_owned())])));
+    assert!(res.headers.get_raw("Cross-Origin-Resource-Policy").is_none());
+}
+
+#[test]
+fn handler_can_extend_vary() {
+    let server = TestServer::new(restrictive());
+    let (res, _) = server.send(Method::Get, origin("https://www.a.com"));
+    let mut expected = match cors_vary() {
+        Vary::Items(items) => items,
+        Vary::Any => unreachable!(),
+    };
+    expected.push(UniCase("Accept-Encoding".to_owned()));
+    assert_eq!(res.headers.get::<Vary>(), Some(&Vary::Items(expected)));
+}
+
+#[test]
+fn disallowed_normal_request_is_rejected() {
+    let server = TestServer::new(restrictive());
+    let (res, body) = server.send(Method::Get, origin("https://www.b.com"));
+    assert_eq!(server.calls(), 0);
+    assert_eq!(res.status, StatusCode::BadRequest);
+    assert_eq!(body, "Normal request requesting disallowed origin 'https://www.b.com'");
+}
+
+#[test]
+fn request_without_origin_passes_through() {
+    let server = TestServer::new(restrictive());
+    let (res, body) = server.send(Method::Get, Headers::new());
+    assert_eq!(server.calls(), 1);
+    assert_eq!(body, "Hello world!");
+    assert!(res.headers.get::<AccessControlAllowOrigin>().is_none());
+}
+
+#[test]
+fn disallowed_websocket_handshake_is_forbidden() {
+    let server = TestServer::new(CorsMiddleware { guard_websockets: true, ..restrictive() });
+    let mut headers = origin("https://www.b.com");
+    headers.set(Connection(vec![ConnectionOption::ConnectionHeader(UniCase("Upgrade"
+        .to_owned()))]));
+    headers.set(Upgrade(vec![Protocol::new(ProtocolName::WebSocket, None)]));
+    let (res, body) = server.send(Method::Get, headers);
+    assert_eq!(server.calls(), 0);
+    assert_eq!(res.status, StatusCode::Forbidden);
+    assert_eq!(body, "WebSocket handshake from disallowed origin 'https://www.b.com'");
+}
+
+#[test]
+fn websocket_handshake_among_other_protocols_is_guarded() {
+    let server = TestServer::new(CorsMiddleware { guard_websockets: true, ..restrictive() });
+    let mut headers = origin("https://www.b.com");
+    headers.set_raw("Upgrade", vec![b"h2c, WebSocket/13".to_vec()]);
+    let (res, _) = server.send(Method::Get, headers);
+    assert_eq!(server.calls(), 0);
+    assert_eq!(res.status, StatusCode::Forbidden);
+}
@@ -0,0 +1,79 @@
+#![cfg(all(feature = "log", feature = "iron"))]
+
+extern crate corsware;
+extern crate iron;
+extern crate hyper;
+extern crate log;
+extern crate router;
+extern crate mount;
+extern crate unicase;
+
+use corsware::{CorsMiddleware, AllowedOrigins, Origin, OriginRule};
+use hyper::Client;
+use hyper::header::Headers;
+use iron::headers::{AccessControlRequestMethod, AccessControlRequestHeaders};
+use iron::headers::Origin as OriginHeader;
+use iron::method::Method::*;
+use log::{Log, Level, Metadata, Record};
+use std::str::FromStr;
+use std::sync::Mutex;
+use unicase::UniCase;
+
+mod autoserver;
+use autoserver::{AutoServer, cors};
+
+struct RecordingLogger {
+    records: Mutex<Vec<(Level, String)>>,
+}
+
+impl Log for RecordingLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.target().starts_with("corsware")
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            self.records.lock().unwrap().push((record.level(), record.args().to_string()));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: RecordingLogger = RecordingLogger { records: Mutex::new(Vec::new()) };
+
+#[test]
+fn decisions_are_logged() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(log::LevelFilter::Debug);
+    let rule = OriginRule::Exact(Origin::parse("https://www.a.com").unwrap());
+    let server = AutoServer::with_cors(CorsMiddleware {
+        allowed_origins: AllowedOrigins::Rules(vec![rule]),
+        ..cors()
+    });
+    for &(origin, header) in &[("https://www.a.com", "Authorization"),
+                               ("https://www.a.com", "X-Custom"),
+                               ("https://www.b.com", "Authorization")] {
+        let mut headers = Headers::new();
+        headers.set(AccessControlRequestMethod(Get));
+        headers.set(AccessControlRequestHeaders(vec![UniCase(header.to_owned())]));
+        headers.set(OriginHeader::from_str(origin).unwrap());
+        Client::new()
+            .request(Options, &format!("http://127.0.0.1:{}/a", server.port))
+            .headers(headers)
+            .send()
+            .unwrap();
+    }
+    let records = LOGGER.records.lock().unwrap();
+    let find = |level: Level, part: &str| {
+        records.iter().any(|&(l, ref msg)| l == level && msg.contains(part))
+    };
+    assert!(find(Level::Debug, "Allowed preflight request to /a from origin"), "{:?}", *records);
+    assert!(find(Level::Debug, "by rule Exact("), "{:?}", *records);
+    assert!(find(Level::Debug, "Access-Control-Allow-Origin https://www.a.com"),
+            "{:?}",
+            *records);
+    assert!(find(Level::Info, "disallowed header(s) X-Custom"), "{:?}", *records);
+    assert!(find(Level::Info, "disallowed origin 'https://www.b.com'"), "{:?}", *records);
+    assert!(find(Level::Debug, "no rule matched"), "{:?}", *records);
+}
@@ -0,0 +1,194 @@
+#![cfg(feature = "tower")]
+
+extern crate corsware;
+extern crate futures_executor;
+extern crate http;
+extern crate tower;
+extern crate unicase;
+use corsware::{CorsMiddleware, CorsLayer, AllowedOrigins, Origin, ResourcePolicy,
+               CrossOriginResourcePolicy, CorsMetrics};
+use http::{Method, Request, Response, StatusCode};
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tower::{Layer, ServiceExt, service_fn};
+use unicase::UniCase;
+
+fn restrictive() -> CorsMiddleware {
+    let mut origins = HashSet::new();
+    origins.insert(Origin::parse("https://www.a.com").unwrap());
+    CorsMiddleware {
+        allowed_origins: AllowedOrigins::Specific(origins),
+        allowed_methods: vec![Method::GET, Method::PUT],
+        exposed_headers: vec![UniCase("X-Exposed".to_owned())],
+        ..CorsMiddleware::permissive()
+    }
+}
+
+/// Sends a request through a `CorsLayer` wrapping a service which responds
+/// with the given response, returning the response and the number of times
+/// the service was called
+fn send(cors: CorsMiddleware,
+        inner: Response<String>,
+        req: Request<()>)
+        -> (Response<String>, usize) {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let service = CorsLayer::new(cors).layer(service_fn(move |_: Request<()>| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        future::ready(Ok::<_, Infallible>(inner.clone()))
+    }));
+    let res = futures_executor::block_on(service.oneshot(req)).unwrap();
+    (res, calls.load(Ordering::SeqCst))
+}
+
+fn hello() -> Response<String> {
+    Response::new("Hello world!".to_owned())
+}
+
+fn request(method: Method, origin: Option<&str>) -> http::request::Builder {
+    let builder = Request::builder().method(method).uri("/a");
+    match origin {
+        Some(origin) => builder.header("Origin", origin),
+        None => builder,
+    }
+}
+
+fn preflight(origin: &str, method: &str) -> Request<()> {
+    request(Method::OPTIONS, Some(origin))
+        .header("Access-Control-Request-Method", method)
+        .body(())
+        .unwrap()
+}
+
+const VARY: &str = "Origin, Access-Control-Request-Method, Access-Control-Request-Headers";
+
+#[test]
+fn preflight_is_answered_without_calling_service() {
+    let (res, calls) = send(restrictive(), hello(), preflight("https://www.a.com", "PUT"));
+    assert_eq!(calls, 0);
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    assert_eq!(res.headers()["Access-Control-Allow-Origin"], "https://www.a.com");
+    assert_eq!(res.headers()["Access-Control-Allow-Methods"], "GET, PUT");
+    assert_eq!(res.headers()["Access-Control-Max-Age"], "3600");
+    assert_eq!(res.headers()["Vary"], VARY);
+    assert_eq!(res.body(), "");
+}
+
+#[test]
+fn disallowed_preflight_is_rejected_without_calling_service() {
+    let (res, calls) = send(restrictive(), hello(), preflight("https://www.a.com", "DELETE"));
+    assert_eq!(calls, 0);
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(res.body(), "Preflight request requesting disallowed method DELETE");
+    assert_eq!(res.headers()["Content-Type"], "text/plain");
+    assert!(!res.headers().contains_key("Access-Control-Allow-Origin"));
+    assert_eq!(res.headers()["Vary"], VARY);
+}
+
+#[test]
+fn normal_response_is_decorated() {
+    let req = request(Method::GET, Some("https://www.a.com")).body(()).unwrap();
+    let (res, calls) = send(restrictive(), hello(), req);
+    assert_eq!(calls, 1);
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.body(), "Hello world!");
+    assert_eq!(res.headers()["Access-Control-Allow-Origin"], "https://www.a.com");
+    assert_eq!(res.headers()["Access-Control-Expose-Headers"], "X-Exposed");
+    assert!(!res.headers().contains_key("Cross-Origin-Resource-Policy"));
+}
+
+#[test]
+fn disallowed_normal_request_is_rejected() {
+    let req = request(Method::GET, Some("https://www.b.com")).body(()).unwrap();
+    let (res, calls) = send(restrictive(), hello(), req);
+    assert_eq!(calls, 0);
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(res.body(),
+               "Normal request requesting disallowed origin 'https://www.b.com'");
+}
+
+#[test]
+fn request_without_origin_passes_through() {
+    let (res, calls) = send(restrictive(), hello(), request(Method::GET, None).body(()).unwrap());
+    assert_eq!(calls, 1);
+    assert_eq!(res.body(), "Hello world!");
+    assert!(!res.headers().contains_key("Access-Control-Allow-Origin"));
+    assert_eq!(res.headers()["Vary"], VARY);
+}
+
+#[test]
+fn observers_are_notified() {
+    let metrics = Arc::new(CorsMetrics::new(10));
+    let cors = || CorsMiddleware { observers: vec![metrics.clone()], ..restrictive() };
+    send(cors(), hello(), request(Method::GET, Some("https://www.a.com")).body(()).unwrap());
+    send(cors(), hello(), preflight("https://www.a.com", "DELETE"));
+    let rendered = metrics.render();
+    assert!(rendered.contains("corsware_normal_requests_total{origin=\"https://www.a.com\"} 1"),
+            "{}",
+            rendered);
+    assert!(rendered.contains("reason=\"method\"} 1"), "{}", rendered);
+}
+
+#[test]
+fn vary_is_merged_with_service_vary() {
+    let mut inner = hello();
+    inner.headers_mut().insert("Vary", "Accept-Encoding, origin".parse().unwrap());
+    let req = request(Method::GET, Some("https://www.a.com")).body(()).unwrap();
+    let (res, _) = send(restrictive(), inner, req);
+    assert_eq!(res.headers()["Vary"],
+               "Accept-Encoding, origin, Access-Control-Request-Method, \
+                Access-Control-Request-Headers");
+}
+
+#[test]
+fn vary_wildcard_is_kept() {
+    let mut inner = hello();
+    inner.headers_mut().insert("Vary", "*".parse().unwrap());
+    let (res, _) = send(restrictive(), inner, request(Method::GET, None).body(()).unwrap());
+    assert_eq!(res.headers()["Vary"], "*");
+}
+
+#[test]
+fn resource_policy_of_service_is_kept() {
+    let mut inner = hello();
+    inner.headers_mut()
+        .insert("Cross-Origin-Resource-Policy",
+                CrossOriginResourcePolicy::CrossOrigin.to_string().parse().unwrap());
+    let cors = CorsMiddleware {
+        resource_policy: ResourcePolicy::FromAllowedOrigins,
+        ..restrictive()
+    };
+    let (res, _) = send(cors, inner, request(Method::GET, None).body(()).unwrap());
+    assert_eq!(res.headers()["Cross-Origin-Resource-Policy"], "cross-origin");
+}
+
+#[test]
+fn disallowed_websocket_handshake_is_forbidden() {
+    let cors = CorsMiddleware { guard_websockets: true, ..restrictive() };
+    let req = request(Method::GET, Some("https://www.b.com"))
+        .header("Connection", "Upgrade")
+        .header("Upgrade", "websocket")
+        .body(())
+        .unwrap();
+    let (res, calls) = send(cors, hello(), req);
+    assert_eq!(calls, 0);
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    assert_eq!(res.body(), "WebSocket handshake from disallowed origin 'https://www.b.com'");
+}
+
+#[test]
+fn allowed_websocket_handshake_gets_no_cors_headers() {
+    let cors = CorsMiddleware { guard_websockets: true, ..restrictive() };
+    let req = request(Method::GET, Some("https://www.a.com"))
+        .header("Connection", "Upgrade")
+        .header("Upgrade", "websocket")
+        .body(())
+        .unwrap();
+    let (res, calls) = send(cors, hello(), req);
+    assert_eq!(calls, 1);
+    assert!(!res.headers().contains_key("Access-Control-Allow-Origin"));
+    assert!(!res.headers().contains_key("Vary"));
+}